@@ -1,4 +1,21 @@
 pub mod indexer;
+pub mod path;
+pub mod fuzzy;
+pub mod highlight;
+pub mod grep;
+pub mod unicode;
+pub mod simd;
+pub mod snapshot;
+pub mod tree;
+pub mod query;
+pub mod terms;
+pub mod meta;
+pub mod frecency;
+pub mod cache;
+pub mod control;
+pub mod roots;
+pub mod watcher;
+pub mod filter;
 pub mod term_emu;
 pub mod opener;
 pub mod flags;
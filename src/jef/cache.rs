@@ -0,0 +1,278 @@
+/*
+ * On disk copy of the index so a launch can show fuzzy results straight away instead of waiting
+ * on a fresh walk. One file per indexed root lives under $XDG_CACHE_HOME/jef/ (or ~/.cache/jef/),
+ * named after a hash of the absolute root path. Every directory keeps the mtime it had when it
+ * was cached. A directory's mtime moves whenever something is added, removed or renamed directly
+ * inside it, so comparing those is enough to find which parts of the tree went stale and only
+ * those directories get listed again. Loading stats the directories and nothing else. Files keep
+ * the metadata they were cached with unless their directory went stale, which is also what
+ * editors that save by renaming a temporary file do. Past that the watcher keeps it current.
+ *
+ * Ignore rules are different, an edited .gitignore changes what belongs in the tree without
+ * touching a single directory mtime. So the cache also keeps the mtime of every ignore file it was
+ * built under, and of every spot above the root where one would count. Any of those changing
+ * throws the cache away. A directory that gains an ignore file is walked again whole.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use serde::{Serialize, Deserialize};
+use rayon::iter::{
+    IntoParallelRefIterator,
+    ParallelIterator
+};
+use jwalk::DirEntry;
+use crate::jef::{
+    filter::{is_rule_file, IndexFilter, WalkState},
+    meta::EntryMeta,
+    path::EntryPath,
+};
+
+// bump whenever CachedIndex changes shape so old files are ignored instead of misread
+const CACHE_VERSION: u32 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedEntry {
+    pub path: EntryPath,
+    pub depth: u16,
+    pub dir_mtime: Option<u64>,
+    pub meta: Option<EntryMeta>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedIndex {
+    version: u32,
+    root: PathBuf,
+    signature: String,
+    // the ignore files the entries were filtered with, None for one that did not exist
+    rules: Vec<(PathBuf, Option<u64>)>,
+    entries: Vec<CachedEntry>,
+}
+
+impl CachedEntry {
+    pub fn from_dir_entry(entry: &DirEntry<(WalkState, Option<EntryMeta>)>, base_depth: u16) -> Option<CachedEntry> {
+        let path = EntryPath::new(&entry.path());
+        let meta = entry.client_state;
+        let mut dir_mtime = None;
+        if entry.file_type().is_dir() {
+            dir_mtime = meta.map(|meta| meta.mtime).or_else(|| mtime_of(&entry.path()));
+        }
+        return Some(CachedEntry {
+            path,
+            depth: base_depth + entry.depth as u16,
+            dir_mtime,
+            meta,
+        });
+    }
+}
+
+pub fn mtime_of(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    return Some(since_epoch.as_nanos() as u64);
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME") {
+        if !cache_home.is_empty() {
+            return Some(PathBuf::from(cache_home).join("jef"));
+        }
+    }
+    let home = std::env::var_os("HOME")?;
+    return Some(PathBuf::from(home).join(".cache").join("jef"));
+}
+
+// fnv-1a, picked over DefaultHasher because file names have to survive a compiler upgrade
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+pub fn cache_file_for(root: &Path, signature: &str) -> Option<PathBuf> {
+    let key = [root.as_os_str().as_bytes(), b"\0", signature.as_bytes()].concat();
+    return Some(cache_dir()?.join(format!("{:016x}.idx", fnv1a(&key))));
+}
+
+pub fn load(root: &Path, filter: &IndexFilter) -> Option<Vec<CachedEntry>> {
+    let signature = filter.signature();
+    let bytes = fs::read(cache_file_for(root, signature)?).ok()?;
+    let cached: CachedIndex = bincode::deserialize(&bytes).ok()?;
+    if cached.version != CACHE_VERSION || cached.root != root || cached.signature != signature {
+        return None;
+    }
+    if cached.rules.iter().any(|(file, mtime)| mtime_of(file) != *mtime) {
+        return None;
+    }
+    return Some(cached.entries);
+}
+
+pub fn save(root: &Path, filter: &IndexFilter, entries: &[CachedEntry]) {
+    let signature = filter.signature();
+    let Some(file) = cache_file_for(root, signature) else {
+        return;
+    };
+    let rules = filter.rule_files(entries.iter().map(|entry| entry.path.as_path()))
+        .into_iter()
+        .map(|file| {
+            let mtime = mtime_of(&file);
+            (file, mtime)
+        })
+        .collect();
+    if let Some(dir) = file.parent() {
+        if fs::create_dir_all(dir).is_err() {
+            return;
+        }
+    }
+    let cached = CachedIndex {
+        version: CACHE_VERSION,
+        root: root.to_path_buf(),
+        signature: signature.to_string(),
+        rules,
+        entries: entries.to_vec(),
+    };
+    if let Ok(bytes) = bincode::serialize(&cached) {
+        // write then rename so a crash never leaves half a cache behind
+        let tmp = file.with_extension("tmp");
+        if fs::write(&tmp, bytes).is_ok() {
+            let _ = fs::rename(&tmp, &file);
+        }
+    }
+}
+
+fn is_under(path: &Path, removed: &HashSet<&Path>) -> bool {
+    return path.ancestors().any(|ancestor| removed.contains(ancestor));
+}
+
+pub fn walk_subtree(filter: &IndexFilter, path: &Path, depth: u16) -> Vec<CachedEntry> {
+    let mut entries = Vec::new();
+    if !filter.can_descend(path) {
+        return entries;
+    }
+    for entry in filter.walker(path, depth).min_depth(1).into_iter().flatten() {
+        if let Some(cached) = CachedEntry::from_dir_entry(&entry, depth) {
+            entries.push(cached);
+        }
+    }
+    return entries;
+}
+
+/// Brings cached entries up to date with the disk. Every directory is stat'ed again, and only
+/// those whose mtime moved are listed again, with their files stat'ed along the way. Only
+/// directories that did not exist before or have a new ignore file are walked. Returns the new
+/// entries and whether anything changed at all.
+pub fn refresh(mut entries: Vec<CachedEntry>, filter: &IndexFilter) -> (Vec<CachedEntry>, bool) {
+    let metas: Vec<Option<Option<EntryMeta>>> = entries.par_iter()
+        .map(|entry| entry.dir_mtime.map(|_mtime| EntryMeta::of(entry.path.as_path(), filter.follows_symlinks())))
+        .collect();
+    let mut meta_changed = false;
+    let mut stale: Vec<(EntryPath, u16, Option<u64>)> = Vec::new();
+    for (entry, meta) in entries.iter_mut().zip(metas) {
+        let Some(meta) = meta else {
+            continue;
+        };
+        if let Some(cached_mtime) = entry.dir_mtime {
+            let mtime = meta.map(|meta| meta.mtime);
+            if mtime != Some(cached_mtime) {
+                stale.push((entry.path.clone(), entry.depth, mtime));
+            }
+        }
+        if entry.meta != meta {
+            entry.meta = meta;
+            meta_changed = true;
+        }
+    }
+    if stale.is_empty() {
+        return (entries, meta_changed);
+    }
+
+    let known: HashSet<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+    let mut removed: HashSet<&Path> = HashSet::new();
+    let mut added: Vec<CachedEntry> = Vec::new();
+    let mut listed: HashSet<PathBuf> = HashSet::new();
+    // files that were there before in a directory that got listed again, stat'ed again too
+    let mut restat: HashMap<PathBuf, Option<EntryMeta>> = HashMap::new();
+    let mut relisted: HashSet<&Path> = HashSet::new();
+    let mut rewalked: HashSet<&Path> = HashSet::new();
+    // parents first, so a directory that gets walked again takes the stale ones below it along
+    stale.sort_by_key(|(_dir, depth, _mtime)| *depth);
+    for (dir, depth, mtime) in &stale {
+        if mtime.is_none() {
+            removed.insert(dir.as_path());
+            continue;
+        }
+        if is_under(dir.as_path(), &rewalked) {
+            continue;
+        }
+        if let Ok(read_dir) = fs::read_dir(dir.as_path()) {
+            let children: Vec<fs::DirEntry> = read_dir.flatten().collect();
+            // a new ignore file decides about everything below it, not just its siblings
+            let new_rules = filter.respects_ignore() && children.iter().any(|child| {
+                let child_path = dir.join(&child.file_name());
+                is_rule_file(child_path.as_path()) && !known.contains(child_path.as_path())
+            });
+            if new_rules {
+                rewalked.insert(dir.as_path());
+                added.extend(walk_subtree(filter, dir.as_path(), *depth));
+                continue;
+            }
+            relisted.insert(dir.as_path());
+            let rules = filter.rules_for(dir.as_path());
+            for child in children {
+                let child_path = dir.join(&child.file_name());
+                let mut is_dir = child.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if filter.follows_symlinks() {
+                    is_dir = child.path().is_dir();
+                }
+                if filter.skips(&rules, child_path.as_path(), is_dir, depth + 1) {
+                    continue;
+                }
+                listed.insert(child_path.as_path().to_path_buf());
+                if !known.contains(child_path.as_path()) {
+                    let meta = EntryMeta::of(child_path.as_path(), filter.follows_symlinks());
+                    let mut dir_mtime = None;
+                    if is_dir {
+                        dir_mtime = meta.map(|meta| meta.mtime);
+                        added.extend(walk_subtree(filter, child_path.as_path(), depth + 1));
+                    }
+                    added.push(CachedEntry { path: child_path, depth: depth + 1, dir_mtime, meta });
+                } else if !is_dir {
+                    restat.insert(child_path.as_path().to_path_buf(), EntryMeta::of(child_path.as_path(), filter.follows_symlinks()));
+                }
+            }
+        }
+    }
+    let stale_dirs: HashMap<&Path, Option<u64>> = stale.iter().map(|(dir, _, mtime)| (dir.as_path(), *mtime)).collect();
+    let mut gone: HashSet<PathBuf> = HashSet::new();
+    for entry in &entries {
+        let path = entry.path.as_path();
+        if path.parent().is_some_and(|parent| relisted.contains(parent)) && !listed.contains(path) {
+            gone.insert(path.to_path_buf());
+        }
+    }
+    removed.extend(gone.iter().map(|path| path.as_path()));
+
+    let mut refreshed: Vec<CachedEntry> = entries.into_iter()
+        .filter(|entry| !is_under(entry.path.as_path(), &removed))
+        // the walk brought back whatever is still there
+        .filter(|entry| !entry.path.as_path().parent().is_some_and(|parent| is_under(parent, &rewalked)))
+        .collect();
+    for entry in refreshed.iter_mut() {
+        if let Some(mtime) = stale_dirs.get(entry.path.as_path()) {
+            entry.dir_mtime = *mtime;
+        }
+        if let Some(meta) = restat.remove(entry.path.as_path()) {
+            entry.meta = meta;
+        }
+    }
+    refreshed.extend(added);
+    return (refreshed, true);
+}
@@ -0,0 +1,155 @@
+/*
+ * How the UI tells the worker threads what changed. Every worker owns an Inbox and blocks on it,
+ * so it wakes up the moment a key press changes something and sleeps for as long as nothing
+ * does. The Controller hands every message to every worker and each one ignores what is not its
+ * business. A worker in the middle of something long, like the first walk of a big tree, checks
+ * its inbox between chunks of work so a new directory or a quit does not wait for it to finish.
+ *
+ * Pause holds a worker inside its inbox until Resume (or Halt) comes in, which is what happens
+ * while an editor or shell has the terminal. Whatever arrives in the meantime is handed over
+ * together once it resumes.
+ */
+
+use std::path::PathBuf;
+use crossbeam_channel::{unbounded, Receiver, Select, Sender, TryRecvError};
+use crate::jef::{
+    flags::SearchMode,
+    roots::RootScope,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Control {
+    SearchChanged { term: String, mode: SearchMode },
+    DirChanged(PathBuf),
+    // `:root`, which trees the finder covers from now on
+    RootChanged(RootScope),
+    // `:index`, walk the trees again from scratch without their caches
+    Reindex,
+    // the ignore toggle flipped, the trees get rebuilt with the other filter
+    FilterChanged,
+    // the indexer published a new snapshot, sent to the search thread only
+    IndexChanged,
+    // the finder list is scrolling towards the end of what it holds of this search's results,
+    // sent to the search thread only
+    MoreResults(u64),
+    Pause,
+    Resume,
+    Halt,
+}
+
+pub enum Wake<T> {
+    Control(Vec<Control>),
+    Other(T),
+}
+
+pub struct Inbox {
+    receiver: Receiver<Control>,
+    sender: Sender<Control>,
+    paused: bool,
+}
+
+impl Inbox {
+    pub fn new() -> Inbox {
+        let (sender, receiver) = unbounded();
+        return Inbox { receiver, sender, paused: false };
+    }
+
+    /// For whoever else needs to reach this worker directly, like the indexer telling the search
+    /// thread about a new snapshot.
+    pub fn sender(&self) -> Sender<Control> {
+        return self.sender.clone();
+    }
+
+    /// Blocks until there is something to do, then hands over everything that piled up.
+    pub fn wait(&mut self) -> Vec<Control> {
+        let first = self.receiver.recv().unwrap_or(Control::Halt);
+        return self.settle(first);
+    }
+
+    /// Like wait, but also wakes up for any of `others` and says which one it was. The inbox goes
+    /// first when both have something, so a Halt never queues behind a burst of events. While
+    /// paused only the inbox is listened to.
+    pub fn wait_with<T>(&mut self, others: &[&Receiver<T>]) -> Wake<(usize, T)> {
+        if let Ok(first) = self.receiver.try_recv() {
+            return Wake::Control(self.settle(first));
+        }
+        if !self.paused {
+            let woken = {
+                let mut select = Select::new();
+                let inbox = select.recv(&self.receiver);
+                for other in others {
+                    select.recv(other);
+                }
+                let operation = select.select();
+                match operation.index() {
+                    index if index == inbox => Err(operation.recv(&self.receiver).unwrap_or(Control::Halt)),
+                    index => Ok(operation.recv(others[index - 1]).ok().map(|event| (index - 1, event))),
+                }
+            };
+            match woken {
+                Err(first) => return Wake::Control(self.settle(first)),
+                Ok(Some(event)) => return Wake::Other(event),
+                // that sender is gone, only the inbox is left to wait for
+                Ok(None) => {},
+            }
+        }
+        return Wake::Control(self.wait());
+    }
+
+    /// Whatever is queued right now, without blocking unless a Pause is among it.
+    pub fn check(&mut self) -> Vec<Control> {
+        match self.receiver.try_recv() {
+            Ok(first) => return self.settle(first),
+            Err(TryRecvError::Empty) => return Vec::new(),
+            Err(TryRecvError::Disconnected) => return vec![Control::Halt],
+        }
+    }
+
+    // takes everything queued after `first`, and keeps taking for as long as a Pause has not been
+    // resumed; Pause and Resume themselves never reach the worker
+    fn settle(&mut self, first: Control) -> Vec<Control> {
+        let mut messages = Vec::new();
+        let mut next = Some(first);
+        while let Some(message) = next {
+            match message {
+                Control::Pause => self.paused = true,
+                Control::Resume => self.paused = false,
+                Control::Halt => {
+                    self.paused = false;
+                    messages.push(Control::Halt);
+                    return messages;
+                },
+                message => messages.push(message),
+            }
+            next = match self.paused {
+                true => Some(self.receiver.recv().unwrap_or(Control::Halt)),
+                false => self.receiver.try_recv().ok(),
+            };
+        }
+        return messages;
+    }
+}
+
+/// The UI's end, one sender per worker inbox.
+#[derive(Clone, Default)]
+pub struct Controller {
+    workers: Vec<Sender<Control>>,
+}
+
+impl Controller {
+    pub fn new() -> Controller {
+        return Controller::default();
+    }
+
+    pub fn inbox(&mut self) -> Inbox {
+        let inbox = Inbox::new();
+        self.workers.push(inbox.sender());
+        return inbox;
+    }
+
+    pub fn send(&self, message: Control) {
+        for worker in &self.workers {
+            let _ = worker.send(message.clone());
+        }
+    }
+}
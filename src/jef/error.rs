@@ -1,10 +1,12 @@
 use std::error::Error;
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct JefError {
     message: String,
 }
 impl Error for JefError {}
 
+#[allow(dead_code)]
 impl JefError {
     pub fn new(message: &str) -> Box<JefError>{
         let error = JefError{
@@ -0,0 +1,366 @@
+/*
+ * Decides what the indexer is allowed to see. With ignore rules on, the walk follows gitignore
+ * semantics: a .gitignore, .ignore or .jefignore in any directory applies to everything below it
+ * (later files in that list win over earlier ones, inner directories win over outer ones), the
+ * user's global git excludes apply everywhere, and .git itself is never indexed. Ignore files in
+ * the directories above the index root are honoured too, up to the root of the git repository.
+ * On top of that the [indexer] section of jef.toml can exclude or include globs, cap the depth,
+ * keep the walk on one filesystem and follow symlinks. All of it rides along with jwalk as its
+ * per directory state, so skipped directories are cut from the walk before they are ever read.
+ */
+
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use jwalk::WalkDirGeneric;
+use crate::jef::{
+    meta::EntryMeta,
+    opener::IndexerConfig,
+};
+
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".jefignore"];
+
+/// Whether `path` is a file ignore rules are read from.
+pub fn is_rule_file(path: &Path) -> bool {
+    return path.file_name().is_some_and(|name| IGNORE_FILES.iter().any(|file| name == *file));
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct IgnoreRules {
+    // outermost directory first
+    matchers: Vec<Arc<Gitignore>>,
+    enabled: bool,
+}
+
+// what jwalk hands from a directory down to its children
+#[derive(Clone, Default, Debug)]
+pub struct WalkState {
+    rules: IgnoreRules,
+    // (dev, inode) of every directory above, only filled in while following symlinks
+    ancestors: Vec<(u64, u64)>,
+}
+
+// every entry comes out of the walk with its metadata, read on the walker threads
+pub type FilteredWalk = WalkDirGeneric<(WalkState, Option<EntryMeta>)>;
+
+impl IgnoreRules {
+    fn descend(&self, dir: &Path) -> IgnoreRules {
+        let mut rules = self.clone();
+        if !self.enabled {
+            return rules;
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in IGNORE_FILES {
+            let file = dir.join(name);
+            if file.is_file() {
+                builder.add(file);
+                found = true;
+            }
+        }
+        if found {
+            if let Ok(gitignore) = builder.build() {
+                rules.matchers.push(Arc::new(gitignore));
+            }
+        }
+        return rules;
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        for matcher in self.matchers.iter().rev() {
+            match matcher.matched(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        return false;
+    }
+}
+
+#[derive(Debug)]
+struct Scope {
+    exclude: GlobSet,
+    include: Option<GlobSet>,
+    max_depth: Option<usize>,
+    one_file_system: bool,
+    follow_symlinks: bool,
+}
+
+// a pattern without a slash matches the name at any depth, like it would in a .gitignore
+fn glob_set(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        let pattern = if pattern.contains('/') {
+            pattern.to_string()
+        } else {
+            format!("**/{}", pattern)
+        };
+        if let Ok(glob) = GlobBuilder::new(&pattern).literal_separator(true).build() {
+            builder.add(glob);
+        }
+    }
+    return builder.build().unwrap_or_else(|_| GlobSet::empty());
+}
+
+#[derive(Clone, Debug)]
+pub struct IndexFilter {
+    // absolute directory the relative walk paths hang off of
+    base: PathBuf,
+    base_dev: Option<u64>,
+    respect_ignore: bool,
+    root_rules: IgnoreRules,
+    scope: Arc<Scope>,
+    signature: String,
+    // directories found but not read yet, only kept up to date when someone asked for it
+    pending: Option<Arc<AtomicUsize>>,
+}
+
+impl IndexFilter {
+    pub fn new(base: &Path, respect_ignore: bool, config: &IndexerConfig) -> IndexFilter {
+        let include = if config.include.is_empty() {
+            None
+        } else {
+            Some(glob_set(&config.include))
+        };
+        let scope = Scope {
+            exclude: glob_set(&config.exclude),
+            include,
+            max_depth: config.max_depth,
+            one_file_system: config.one_file_system,
+            follow_symlinks: config.follow_symlinks,
+        };
+        let mut signature = String::from("all");
+        if respect_ignore {
+            signature = String::from("ignore");
+        }
+        let mut filter = IndexFilter {
+            base: base.to_path_buf(),
+            base_dev: fs::metadata(base).ok().map(|metadata| metadata.dev()),
+            respect_ignore,
+            root_rules: IgnoreRules::default(),
+            scope: Arc::new(scope),
+            // cache files are kept apart per setting so toggling never loads the other tree
+            signature: format!("{}:{:?}", signature, config),
+            pending: None,
+        };
+        if respect_ignore {
+            filter.root_rules = filter.build_root_rules();
+        }
+        return filter;
+    }
+
+    fn build_root_rules(&self) -> IgnoreRules {
+        let mut rules = IgnoreRules { matchers: Vec::new(), enabled: true };
+        let (global, _err) = GitignoreBuilder::new(&self.base).build_global();
+        if !global.is_empty() {
+            rules.matchers.push(Arc::new(global));
+        }
+        for parent in self.rule_parents().iter().rev() {
+            rules = rules.descend(parent);
+        }
+        return rules;
+    }
+
+    // the directories above the root whose ignore files count, innermost first. Parents only
+    // count while we are inside a repository
+    fn rule_parents(&self) -> Vec<&Path> {
+        let mut parents: Vec<&Path> = Vec::new();
+        let mut in_repo = self.base.join(".git").exists();
+        for parent in self.base.ancestors().skip(1) {
+            if in_repo {
+                break;
+            }
+            parents.push(parent);
+            in_repo = parent.join(".git").exists();
+        }
+        if !in_repo {
+            parents.clear();
+        }
+        return parents;
+    }
+
+    /// Every file the rules of this tree come from: the ignore files among `found`, and every
+    /// spot in the root and above it where one would count, whether there is one yet or not.
+    /// Nothing with the rules off.
+    pub fn rule_files<'a>(&self, found: impl Iterator<Item = &'a Path>) -> Vec<PathBuf> {
+        if !self.respect_ignore {
+            return Vec::new();
+        }
+        let mut files: Vec<PathBuf> = std::iter::once(self.base.as_path())
+            .chain(self.rule_parents())
+            .flat_map(|dir| IGNORE_FILES.iter().map(move |name| dir.join(name)))
+            .collect();
+        files.extend(found.filter(|path| is_rule_file(path)).map(|path| self.absolute(path)));
+        files.sort();
+        files.dedup();
+        return files;
+    }
+
+    fn absolute(&self, path: &Path) -> PathBuf {
+        return self.base.join(path);
+    }
+
+    // rules in effect inside `dir`, built by descending from the root one directory at a time
+    pub fn rules_for(&self, dir: &Path) -> IgnoreRules {
+        let mut rules = self.root_rules.clone();
+        if !self.respect_ignore {
+            return rules;
+        }
+        let dir = self.absolute(dir);
+        let Ok(relative) = dir.strip_prefix(&self.base) else {
+            return rules.descend(&dir);
+        };
+        let mut current = self.base.clone();
+        rules = rules.descend(&current);
+        for component in relative.components() {
+            current.push(component);
+            rules = rules.descend(&current);
+        }
+        return rules;
+    }
+
+    // exclude/include globs only, matched against the path relative to the index root
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let absolute = self.absolute(path);
+        let relative = absolute.strip_prefix(&self.base).unwrap_or(&absolute);
+        if self.scope.exclude.is_match(relative) {
+            return true;
+        }
+        if let Some(include) = &self.scope.include {
+            return !is_dir && !include.is_match(relative);
+        }
+        return false;
+    }
+
+    pub fn skips(&self, rules: &IgnoreRules, path: &Path, is_dir: bool, depth: u16) -> bool {
+        if let Some(max_depth) = self.scope.max_depth {
+            if depth as usize > max_depth {
+                return true;
+            }
+        }
+        if self.is_excluded(path, is_dir) {
+            return true;
+        }
+        return rules.is_ignored(&self.absolute(path), is_dir);
+    }
+
+    // false for mount points when the walk has to stay on the root's filesystem
+    pub fn can_descend(&self, dir: &Path) -> bool {
+        if !self.scope.one_file_system {
+            return true;
+        }
+        let dev = fs::metadata(self.absolute(dir)).ok().map(|metadata| metadata.dev());
+        return dev == self.base_dev;
+    }
+
+    pub fn respects_ignore(&self) -> bool {
+        return self.respect_ignore;
+    }
+
+    pub fn follows_symlinks(&self) -> bool {
+        return self.scope.follow_symlinks;
+    }
+
+    /// The same filter, but its walks keep `pending` at the number of directories they have found
+    /// and not read yet. It should start at 1, for the directory the walk starts in.
+    pub fn counting(&self, pending: Arc<AtomicUsize>) -> IndexFilter {
+        return IndexFilter { pending: Some(pending), ..self.clone() };
+    }
+
+    // a jwalk walker that prunes skipped entries, starting at `path` which sits at `depth`
+    pub fn walker(&self, path: &Path, depth: u16) -> FilteredWalk {
+        let mut state = WalkState {
+            rules: self.root_rules.clone(),
+            ..WalkState::default()
+        };
+        if self.respect_ignore && self.absolute(path) != self.base {
+            if let Some(parent) = path.parent() {
+                state.rules = self.rules_for(parent);
+            }
+        }
+        let mut walk = FilteredWalk::new(path)
+            .skip_hidden(false)
+            .follow_links(self.scope.follow_symlinks);
+        if let Some(max_depth) = self.scope.max_depth {
+            walk = walk.max_depth(max_depth.saturating_sub(depth as usize));
+        }
+        let filter = self.clone();
+        return walk
+            .root_read_dir_state(state)
+            .process_read_dir(move |read_depth, dir, state, children| {
+                // jwalk calls this once for the root entry itself before reading anything
+                if read_depth.is_none() {
+                    for child in children.iter_mut().flatten() {
+                        child.client_state = EntryMeta::of(&filter.base.join(child.path()), filter.scope.follow_symlinks);
+                    }
+                    return;
+                }
+                let dir = filter.base.join(dir);
+                state.rules = state.rules.descend(&dir);
+                if filter.scope.follow_symlinks {
+                    if let Ok(metadata) = fs::metadata(&dir) {
+                        state.ancestors.push((metadata.dev(), metadata.ino()));
+                    }
+                }
+                children.retain(|child| {
+                    let Ok(child) = child else {
+                        return true;
+                    };
+                    let child_path = dir.join(&child.file_name);
+                    let is_dir = child.file_type.is_dir();
+                    if filter.is_excluded(&child_path, is_dir) {
+                        return false;
+                    }
+                    return !state.rules.is_ignored(&child_path, is_dir);
+                });
+                for child in children.iter_mut().flatten() {
+                    child.client_state = EntryMeta::of(&dir.join(&child.file_name), filter.scope.follow_symlinks);
+                    if !child.file_type.is_dir() || child.read_children_path.is_none() {
+                        continue;
+                    }
+                    let may_loop = filter.scope.follow_symlinks && child.path_is_symlink();
+                    if !filter.scope.one_file_system && !may_loop {
+                        continue;
+                    }
+                    let child_path = dir.join(&child.file_name);
+                    let Ok(metadata) = fs::metadata(&child_path) else {
+                        continue;
+                    };
+                    let crosses_device = filter.scope.one_file_system && Some(metadata.dev()) != filter.base_dev;
+                    let loops = may_loop && state.ancestors.contains(&(metadata.dev(), metadata.ino()));
+                    if crosses_device || loops {
+                        // still listed, just never opened
+                        child.read_children_path = None;
+                    }
+                }
+                if let Some(pending) = &filter.pending {
+                    let found = children.iter().flatten().filter(|child| child.read_children_path.is_some()).count();
+                    pending.fetch_add(found, Ordering::Relaxed);
+                    pending.fetch_sub(1, Ordering::Relaxed);
+                }
+            });
+    }
+
+    pub fn signature(&self) -> &str {
+        return &self.signature;
+    }
+}
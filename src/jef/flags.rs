@@ -1,5 +1,31 @@
-pub enum Flag {
-    Nothing,
-    Halt,
+// which matcher the `f` finder runs the search term through, GREP searches file contents
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchMode {
+    Fuzzy,
+    Infix,
+    Regex,
+    Glob,
+    Grep,
 }
 
+impl SearchMode {
+    pub fn next(&self) -> SearchMode {
+        match self {
+            SearchMode::Fuzzy => SearchMode::Infix,
+            SearchMode::Infix => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Glob,
+            SearchMode::Glob => SearchMode::Grep,
+            SearchMode::Grep => SearchMode::Fuzzy,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SearchMode::Fuzzy => "FIND",
+            SearchMode::Infix => "INFIX",
+            SearchMode::Regex => "REGEX",
+            SearchMode::Glob => "GLOB",
+            SearchMode::Grep => "GREP",
+        }
+    }
+}
@@ -0,0 +1,187 @@
+/*
+ * Remembers what gets opened through JEF, zoxide style. Every file or directory opened bumps its
+ * rank by one, and how recently it was last opened multiplies that rank when it is scored, so
+ * something opened a lot last month and something opened twice this morning can both come out on
+ * top. Once the ranks add up past MAX_TOTAL_RANK everything is scaled down and whatever falls
+ * under 1 is forgotten, which keeps the file small and lets old habits fade. The scores nudge the
+ * fuzzy finder's ranking and pick the directory for `:z`. Lives in $XDG_DATA_HOME/jef/ (or
+ * ~/.local/share/jef/) since, unlike the index cache, it can't be rebuilt from the disk.
+ */
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use serde::{Serialize, Deserialize};
+
+const FRECENCY_VERSION: u32 = 1;
+const MAX_TOTAL_RANK: f64 = 10000.0;
+const AGING_FACTOR: f64 = 0.9;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+pub type SharedFrecency = Arc<Mutex<Frecency>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+struct Visit {
+    rank: f64,
+    // seconds since the epoch
+    last: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Frecency {
+    version: u32,
+    entries: HashMap<String, Visit>,
+    // where load() found it and save() puts it back, None keeps it in memory only
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+pub fn now() -> u64 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs());
+}
+
+fn data_file() -> Option<PathBuf> {
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME") {
+        if !data_home.is_empty() {
+            return Some(PathBuf::from(data_home).join("jef").join("frecency.bin"));
+        }
+    }
+    let home = std::env::var_os("HOME")?;
+    return Some(PathBuf::from(home).join(".local").join("share").join("jef").join("frecency.bin"));
+}
+
+impl Frecency {
+    pub fn load() -> Frecency {
+        let file = data_file();
+        let mut frecency = Frecency::default();
+        if let Some(bytes) = file.as_ref().and_then(|file| fs::read(file).ok()) {
+            if let Ok(loaded) = bincode::deserialize::<Frecency>(&bytes) {
+                if loaded.version == FRECENCY_VERSION {
+                    frecency = loaded;
+                }
+            }
+        }
+        frecency.version = FRECENCY_VERSION;
+        frecency.file = file;
+        return frecency;
+    }
+
+    pub fn save(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if let Some(dir) = file.parent() {
+            if fs::create_dir_all(dir).is_err() {
+                return;
+            }
+        }
+        if let Ok(bytes) = bincode::serialize(self) {
+            let tmp = file.with_extension("tmp");
+            if fs::write(&tmp, bytes).is_ok() {
+                let _ = fs::rename(&tmp, file);
+            }
+        }
+    }
+
+    pub fn visit(&mut self, path: &Path, now: u64) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let Some(path) = path.to_str() else {
+            return;
+        };
+        let visit = self.entries.entry(path.to_string()).or_insert(Visit { rank: 0.0, last: now });
+        visit.rank += 1.0;
+        visit.last = now;
+        let total: f64 = self.entries.values().map(|visit| visit.rank).sum();
+        if total > MAX_TOTAL_RANK {
+            for visit in self.entries.values_mut() {
+                visit.rank *= AGING_FACTOR;
+            }
+            self.entries.retain(|_path, visit| visit.rank >= 1.0);
+        }
+    }
+
+    fn score_of(visit: &Visit, now: u64) -> f64 {
+        let age = now.saturating_sub(visit.last);
+        let recency = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        return visit.rank * recency;
+    }
+
+    /// Every remembered path with its score, keyed by the absolute path like named roots spell it.
+    pub fn scores(&self, now: u64) -> HashMap<String, f64> {
+        return self.entries.iter()
+            .map(|(path, visit)| (path.clone(), Frecency::score_of(visit, now)))
+            .collect();
+    }
+
+    /// Scores of everything remembered under `dir`, keyed the way the index spells its paths
+    /// ("./src/main.rs") so the finder can look them up directly.
+    pub fn scores_under(&self, dir: &Path, now: u64) -> HashMap<String, f64> {
+        let mut scores = HashMap::new();
+        for (path, visit) in &self.entries {
+            if let Ok(relative) = Path::new(path).strip_prefix(dir) {
+                if let Some(relative) = relative.to_str().filter(|relative| !relative.is_empty()) {
+                    scores.insert(format!("./{}", relative), Frecency::score_of(visit, now));
+                }
+            }
+        }
+        return scores;
+    }
+
+    /// The best directory for a `:z` query. Like zoxide, every word has to show up in the path in
+    /// order and the last one has to be in the final component. Directories that are gone get
+    /// forgotten on the way.
+    pub fn best_dir(&mut self, query: &str, exclude: &Path, now: u64) -> Option<PathBuf> {
+        let words: Vec<String> = query.split_whitespace().map(|word| word.to_lowercase()).collect();
+        let mut candidates: Vec<(&String, f64)> = self.entries.iter()
+            .filter(|(path, _visit)| matches_words(path, &words) && Path::new(path) != exclude)
+            .map(|(path, visit)| (path, Frecency::score_of(visit, now)))
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let mut best = None;
+        let mut missing = Vec::new();
+        for (path, _score) in candidates {
+            if Path::new(path).is_dir() {
+                best = Some(PathBuf::from(path));
+                break;
+            }
+            if !Path::new(path).exists() {
+                missing.push(path.clone());
+            }
+        }
+        for path in missing {
+            self.entries.remove(&path);
+        }
+        return best;
+    }
+}
+
+fn matches_words(path: &str, words: &[String]) -> bool {
+    let path = path.to_lowercase();
+    let mut rest = path.as_str();
+    for word in words {
+        match rest.find(word.as_str()) {
+            Some(i) => rest = &rest[i + word.len()..],
+            None => return false,
+        }
+    }
+    if let Some(last) = words.last() {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        return name.contains(last.as_str());
+    }
+    return true;
+}
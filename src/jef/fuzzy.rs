@@ -0,0 +1,372 @@
+/*
+ * Fuzzy subsequence matcher used by the `f` finder. Every character of the query has to show up
+ * in the candidate in the same order, but not necessarily next to each other. The best alignment
+ * is found with a small Smith-Waterman style table (the same idea fzf uses), so "mrs" will line
+ * up with the m, r and s of "main.rs" rather than the first r it happens to find. Matches that
+ * land on word boundaries, camelCase humps or inside the file name itself score higher, runs of
+ * consecutive characters score higher, and gaps and long paths cost a little. Both sides are
+ * compared in their folded form, see unicode.rs.
+ *
+ * A query with a slash in it is matched a path segment at a time instead: the last segment against
+ * the file name and the ones before it against directory names further up, in order, so "jef/ind"
+ * finds src/jef/indexer.rs and "te/mod" finds src/tests/mod.rs but "mod/te" finds neither.
+ * Directories may be skipped between segments at a small cost, and an empty last segment ("jef/")
+ * takes any name.
+ *
+ * When nothing lines up, a query can still be a typo away from the start of a file name:
+ * "temr_emu" for term_emu.rs. That is the Damerau-Levenshtein distance (an adjacent swap counts as
+ * one edit) between the query and the closest prefix of the name, allowed one edit per four query
+ * characters up to the configured maximum. Every edit costs far more than any real match scores,
+ * so typo hits always rank below the rest. Only as much of the name as the query plus its edits
+ * can reach is compared, and before any table is filled the name has to hold all but that many
+ * of the query's characters, which turns away nearly every name the query has nothing to do with.
+ * What is left gets the table, which still gives up as soon as a whole row is over the limit.
+ */
+
+use std::ops::Range;
+use crate::jef::unicode::{fold_chars, fold_spans};
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+
+const BONUS_BOUNDARY_SLASH: i64 = 10;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FILE_NAME: i64 = 2;
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+// every directory left out between two matched segments
+const SCORE_SEGMENT_SKIP: i64 = -8;
+
+// what one typo costs, more than any path could ever score
+const SCORE_TYPO: i64 = -(1 << 32);
+// query characters per typo allowed
+const CHARS_PER_TYPO: usize = 4;
+
+// every this many characters of path costs a point, so shorter paths win ties
+const LENGTH_PENALTY_DIVISOR: i64 = 8;
+
+const NO_MATCH: i64 = i64::MIN / 2;
+
+fn char_bonus(prev: Option<char>, current: char) -> i64 {
+    match prev {
+        None => BONUS_BOUNDARY_SLASH,
+        Some('/') => BONUS_BOUNDARY_SLASH,
+        Some(p) if p == '_' || p == '-' || p == '.' || p == ' ' => BONUS_BOUNDARY,
+        Some(p) if p.is_lowercase() && current.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_ascii_digit() && current.is_ascii_digit() => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+// quick check that every query char exists in order before paying for the table
+fn is_subsequence(candidate: &[char], query: &[char]) -> bool {
+    let mut q = query.iter().peekable();
+    for c in candidate {
+        if let Some(next) = q.peek() {
+            if *next == c {
+                q.next();
+            }
+        } else {
+            break;
+        }
+    }
+    return q.peek().is_none();
+}
+
+/// Scores `candidate` against `query`, returning `None` when the query is not a case insensitive
+/// subsequence of the candidate. A leading "./" is ignored. Higher scores are better matches.
+pub fn fuzzy_score(candidate: &str, query: &str, ignore_diacritics: bool) -> Option<i64> {
+    if query.is_empty() {
+        return None;
+    }
+    let candidate = candidate.strip_prefix("./").unwrap_or(candidate);
+    if query.contains('/') {
+        return segment_score(candidate, query, ignore_diacritics);
+    }
+    // the boundary bonuses look at the original chars, the matching at the folded ones
+    let mut original: Vec<char> = Vec::with_capacity(candidate.len());
+    let mut lowered: Vec<char> = Vec::with_capacity(candidate.len());
+    fold_chars(candidate, ignore_diacritics, |c, f| {
+        original.push(c);
+        lowered.push(f);
+    });
+    let mut folded_query: Vec<char> = Vec::with_capacity(query.len());
+    fold_chars(query, ignore_diacritics, |_c, f| folded_query.push(f));
+    let query = folded_query;
+    if query.is_empty() {
+        return None;
+    }
+
+    let (best, _at) = align(&original, &lowered, &query, false)?;
+    return Some(best - lowered.len() as i64 / LENGTH_PENALTY_DIVISOR);
+}
+
+// the best alignment of the query in the candidate, and with `trace` where each query char landed
+fn align(original: &[char], lowered: &[char], query: &[char], trace: bool) -> Option<(i64, Vec<usize>)> {
+    if query.len() > lowered.len() || !is_subsequence(lowered, query) {
+        return None;
+    }
+
+    let name_start = original.iter().rposition(|c| *c == '/').map(|i| i + 1).unwrap_or(0);
+    let bonus: Vec<i64> = (0..original.len()).map(|i| {
+        let prev = if i == 0 { None } else { Some(original[i - 1]) };
+        let mut bonus = char_bonus(prev, original[i]);
+        if i >= name_start {
+            bonus += BONUS_FILE_NAME;
+        }
+        bonus
+    }).collect();
+
+    // prev_row[i] is the best score with the previous query char matched at candidate[i]
+    let n = lowered.len();
+    let mut prev_row: Vec<i64> = vec![NO_MATCH; n];
+    let mut row: Vec<i64> = vec![NO_MATCH; n];
+    // every row, only kept to trace the alignment back
+    let mut rows: Vec<Vec<i64>> = Vec::new();
+    for (j, q) in query.iter().enumerate() {
+        let mut gap = NO_MATCH;
+        for i in 0..n {
+            if j > 0 && i >= 2 {
+                gap = (gap + SCORE_GAP_EXTENSION).max(prev_row[i - 2] + SCORE_GAP_START);
+            }
+            row[i] = NO_MATCH;
+            if lowered[i] != *q {
+                continue;
+            }
+            if j == 0 {
+                row[i] = SCORE_MATCH + bonus[i] * BONUS_FIRST_CHAR_MULTIPLIER;
+                continue;
+            }
+            let consecutive = if i >= 1 { prev_row[i - 1] + BONUS_CONSECUTIVE } else { NO_MATCH };
+            let best = consecutive.max(gap);
+            if best > NO_MATCH / 2 {
+                row[i] = best + SCORE_MATCH + bonus[i];
+            }
+        }
+        if trace {
+            rows.push(row.clone());
+        }
+        std::mem::swap(&mut prev_row, &mut row);
+    }
+
+    let (mut i, best) = prev_row.iter().copied().enumerate().max_by_key(|(_i, score)| *score)?;
+    if best <= NO_MATCH / 2 {
+        return None;
+    }
+    let mut at = Vec::new();
+    if trace {
+        // walk back through the rows, finding which of the two ways in produced each score
+        at.push(i);
+        for j in (1..query.len()).rev() {
+            let came = rows[j][i] - SCORE_MATCH - bonus[i];
+            let prev = &rows[j - 1];
+            i = match i >= 1 && prev[i - 1] + BONUS_CONSECUTIVE == came {
+                true => i - 1,
+                false => (0..i.saturating_sub(1)).rev().find(|k| {
+                    prev[*k] > NO_MATCH / 2 && prev[*k] + SCORE_GAP_START + SCORE_GAP_EXTENSION * (i - 2 - k) as i64 == came
+                })?,
+            };
+            at.push(i);
+        }
+        at.reverse();
+    }
+    return Some((best, at));
+}
+
+// the segments of a slashed query against the directories and name of the candidate, see the top
+fn segment_score(candidate: &str, query: &str, ignore_diacritics: bool) -> Option<i64> {
+    let (dirs, name) = candidate.rsplit_once('/').unwrap_or(("", candidate));
+    let (dir_query, name_query) = query.rsplit_once('/').unwrap_or(("", query));
+    let mut score = match name_query {
+        "" => 0,
+        name_query => fuzzy_score(name, name_query, ignore_diacritics)?,
+    };
+    let dirs: Vec<&str> = dirs.split('/').filter(|dir| !dir.is_empty()).collect();
+    let segments: Vec<&str> = dir_query.split('/').filter(|segment| !segment.is_empty()).collect();
+    if !segments.is_empty() {
+        // best[i] is the best score with the segments so far matched, the latest at dirs[i]
+        let mut best: Vec<i64> = vec![NO_MATCH; dirs.len()];
+        for (j, segment) in segments.iter().enumerate() {
+            // the best match of the segments before this one ending above dirs[i], skips paid
+            let mut carried = NO_MATCH;
+            let mut row: Vec<i64> = vec![NO_MATCH; dirs.len()];
+            for (i, dir) in dirs.iter().enumerate() {
+                let before = if j == 0 { 0 } else { carried };
+                if before > NO_MATCH / 2 {
+                    if let Some(matched) = fuzzy_score(dir, segment, ignore_diacritics) {
+                        row[i] = before + matched;
+                    }
+                }
+                carried = (carried + SCORE_SEGMENT_SKIP).max(best[i]);
+            }
+            best = row;
+        }
+        let last = dirs.len().saturating_sub(1);
+        let matched = best.iter().enumerate()
+            .map(|(i, score)| score + SCORE_SEGMENT_SKIP * (last - i) as i64)
+            .max()
+            .filter(|score| *score > NO_MATCH / 2)?;
+        score += matched;
+    }
+    return Some(score - candidate.len() as i64 / LENGTH_PENALTY_DIVISOR);
+}
+
+/// Scores a name that is within `max_typos` edits of the query, see the top. The query comes
+/// folded and split into chars once per search, since this runs for nearly every path. `None`
+/// for anything further off, and for slashed queries, which have to line up segment by segment.
+pub fn typo_score(candidate: &str, query: &[char], max_typos: usize, ignore_diacritics: bool) -> Option<i64> {
+    let allowed = typos_allowed(query, max_typos)?;
+    let name = candidate.rsplit('/').next().unwrap_or(candidate);
+    let folded_name = folded_head(name, query.len() + allowed, ignore_diacritics);
+    let (typos, _end) = typo_prefix(&folded_name, query, allowed)?;
+    return Some(SCORE_TYPO * typos as i64 - candidate.len() as i64 / LENGTH_PENALTY_DIVISOR);
+}
+
+/// The start of the name that `typo_score` found the query a typo or two away from.
+pub fn typo_positions(candidate: &str, query: &[char], max_typos: usize, ignore_diacritics: bool) -> Option<Vec<Range<usize>>> {
+    let allowed = typos_allowed(query, max_typos)?;
+    let name_start = candidate.rfind('/').map_or(0, |i| i + 1);
+    let spans = fold_spans(&candidate[name_start..], ignore_diacritics);
+    let folded_name: Vec<char> = spans.iter().map(|span| span.1).collect();
+    let (_typos, end) = typo_prefix(&folded_name, query, allowed)?;
+    return Some(spans[..end].last().map(|last| name_start..name_start + last.2.end).into_iter().collect());
+}
+
+fn typos_allowed(query: &[char], max_typos: usize) -> Option<usize> {
+    if query.contains(&'/') {
+        return None;
+    }
+    let allowed = max_typos.min(query.len() / CHARS_PER_TYPO);
+    return (allowed > 0).then_some(allowed);
+}
+
+// the folded name, only as far as `reach` chars where that is easy to tell: ASCII folds a char at
+// a time, anything else can fold into more or fewer chars than it has and is folded whole
+fn folded_head(name: &str, reach: usize, ignore_diacritics: bool) -> Vec<char> {
+    let name = match name.is_ascii() {
+        true => &name[..name.len().min(reach)],
+        false => name,
+    };
+    let mut folded = Vec::with_capacity(name.len());
+    fold_chars(name, ignore_diacritics, |_c, f| folded.push(f));
+    return folded;
+}
+
+// how many typos away the query is from the folded name and how much of the name that covers
+fn typo_prefix(name: &[char], query: &[char], allowed: usize) -> Option<(usize, usize)> {
+    // no prefix further than that is within the allowed edits
+    let name = &name[..name.len().min(query.len() + allowed)];
+    if !shares_enough(name, query, allowed) {
+        return None;
+    }
+    return prefix_distance(name, query, allowed);
+}
+
+// whether the name could be `max` edits from the query at all: an edit costs the alignment at most
+// one of the query's characters and a swap none, so all but `max` of them have to turn up in the
+// name. Counting that needs no table and rules out nearly every unrelated name
+fn shares_enough(name: &[char], query: &[char], max: usize) -> bool {
+    let mut unused: Vec<char> = name.to_vec();
+    let mut missing = 0;
+    for q in query {
+        match unused.iter().position(|c| c == q) {
+            Some(i) => {
+                unused.swap_remove(i);
+            }
+            None => {
+                missing += 1;
+                if missing > max {
+                    return false;
+                }
+            }
+        }
+    }
+    return true;
+}
+
+// the fewest edits turning `query` into some prefix of `name` and the longest such prefix, if
+// that is at most `max` edits
+fn prefix_distance(name: &[char], query: &[char], max: usize) -> Option<(usize, usize)> {
+    // row[j] is the distance between the query so far and name[..j], two rows back for swaps
+    let mut before: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=name.len()).collect();
+    for (i, q) in query.iter().enumerate() {
+        let mut row: Vec<usize> = vec![i + 1; name.len() + 1];
+        for j in 1..=name.len() {
+            let cost = usize::from(name[j - 1] != *q);
+            row[j] = (prev[j - 1] + cost).min(prev[j] + 1).min(row[j - 1] + 1);
+            if i >= 1 && j >= 2 && name[j - 1] == query[i - 1] && name[j - 2] == *q {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        if row.iter().all(|distance| *distance > max) {
+            return None;
+        }
+        before = std::mem::replace(&mut prev, row);
+    }
+    let (end, distance) = prev.into_iter().enumerate().min_by_key(|(end, distance)| (*distance, std::cmp::Reverse(*end)))?;
+    return (distance <= max).then_some((distance, end));
+}
+
+/// Where `query` lines up in `candidate`, as byte ranges of the matched chars to draw highlighted.
+/// The same alignment `fuzzy_score` scores, and for a slashed query each segment on the nearest
+/// directory that takes it.
+pub fn fuzzy_positions(candidate: &str, query: &str, ignore_diacritics: bool) -> Option<Vec<Range<usize>>> {
+    if query.is_empty() {
+        return None;
+    }
+    let offset = if candidate.starts_with("./") { 2 } else { 0 };
+    let candidate = &candidate[offset..];
+    let mut ranges = match query.contains('/') {
+        true => segment_positions(candidate, query, ignore_diacritics)?,
+        false => aligned(candidate, query, ignore_diacritics)?,
+    };
+    ranges.sort_by_key(|range| range.start);
+    return Some(ranges.into_iter().map(|range| range.start + offset..range.end + offset).collect());
+}
+
+fn aligned(candidate: &str, query: &str, ignore_diacritics: bool) -> Option<Vec<Range<usize>>> {
+    let spans = fold_spans(candidate, ignore_diacritics);
+    let original: Vec<char> = spans.iter().map(|span| span.0).collect();
+    let lowered: Vec<char> = spans.iter().map(|span| span.1).collect();
+    let mut folded_query: Vec<char> = Vec::with_capacity(query.len());
+    fold_chars(query, ignore_diacritics, |_c, f| folded_query.push(f));
+    if folded_query.is_empty() {
+        return None;
+    }
+    let (_best, at) = align(&original, &lowered, &folded_query, true)?;
+    let mut ranges: Vec<Range<usize>> = at.into_iter().map(|i| spans[i].2.clone()).collect();
+    // both halves of an "ß" that folded into "ss" are the same char on screen
+    ranges.dedup();
+    return Some(ranges);
+}
+
+// the name first, then every segment before it on the nearest directory further up that takes it
+fn segment_positions(candidate: &str, query: &str, ignore_diacritics: bool) -> Option<Vec<Range<usize>>> {
+    let (dirs, name) = candidate.rsplit_once('/').unwrap_or(("", candidate));
+    let (dir_query, name_query) = query.rsplit_once('/').unwrap_or(("", query));
+    let shifted = |ranges: Vec<Range<usize>>, by: usize| ranges.into_iter().map(move |range| range.start + by..range.end + by);
+    let mut ranges: Vec<Range<usize>> = match name_query {
+        "" => Vec::new(),
+        name_query => shifted(aligned(name, name_query, ignore_diacritics)?, candidate.len() - name.len()).collect(),
+    };
+    let mut remaining = dirs;
+    for segment in dir_query.split('/').filter(|segment| !segment.is_empty()).rev() {
+        loop {
+            if remaining.is_empty() {
+                return None;
+            }
+            let (rest, dir) = remaining.rsplit_once('/').unwrap_or(("", remaining));
+            let dir_start = remaining.len() - dir.len();
+            remaining = rest;
+            if let Some(found) = aligned(dir, segment, ignore_diacritics) {
+                ranges.extend(shifted(found, dir_start));
+                break;
+            }
+        }
+    }
+    return Some(ranges);
+}
@@ -0,0 +1,99 @@
+/*
+ * Content search for the GREP mode of the finder. It runs over the files the indexer already
+ * knows about, so whatever the ignore rules and the [indexer] scope keep out of the index is
+ * never read here either. Files are searched in parallel and every matching line becomes one
+ * `path:line: snippet` entry in the result list. Files that look binary (a NUL byte near the
+ * start, same heuristic as git and ripgrep) are skipped. Like ripgrep's smart case, an all
+ * lowercase query matches any case and a query with capitals in it is matched exactly.
+ */
+
+use std::{
+    fs,
+    path::Path,
+    sync::Arc,
+};
+use rayon::{
+    iter::{
+        IntoParallelRefIterator,
+        ParallelIterator
+    },
+    slice::ParallelSliceMut,
+};
+use crate::jef::{
+    path::{escape, EntryPath},
+    unicode::truncate_graphemes,
+};
+
+// how far into a file to look for a NUL byte
+const BINARY_PROBE: usize = 8 * 1024;
+// anything bigger is almost certainly not something anyone wants to grep from a file browser
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_SNIPPET: usize = 200;
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    return bytes[..bytes.len().min(BINARY_PROBE)].contains(&0);
+}
+
+fn snippet(line: &str) -> String {
+    return truncate_graphemes(line.trim(), MAX_SNIPPET).to_string();
+}
+
+fn grep_file(path: &Path, query: &str, ignore_case: bool) -> Vec<(usize, String)> {
+    let mut hits = Vec::new();
+    let Ok(metadata) = fs::metadata(path) else {
+        return hits;
+    };
+    if !metadata.is_file() || metadata.len() > MAX_FILE_SIZE {
+        return hits;
+    }
+    let Ok(bytes) = fs::read(path) else {
+        return hits;
+    };
+    if is_binary(&bytes) {
+        return hits;
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    for (i, line) in text.lines().enumerate() {
+        let found = if ignore_case {
+            line.to_lowercase().contains(query)
+        } else {
+            line.contains(query)
+        };
+        if found {
+            hits.push((i + 1, snippet(line)));
+        }
+    }
+    return hits;
+}
+
+/// Greps every path for the query and returns one `path:line: snippet` entry per matching line,
+/// ordered by path and then line. Each entry still leads to its file, see parse_hit.
+pub fn grep_files(paths: &[Arc<EntryPath>], query: &str) -> Vec<Arc<EntryPath>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let ignore_case = !query.chars().any(|c| c.is_uppercase());
+    let query = if ignore_case { query.to_lowercase() } else { query.to_string() };
+    let mut found: Vec<(Arc<EntryPath>, usize, String)> = paths.par_iter()
+        .flat_map_iter(|path| {
+            grep_file(path.as_path(), &query, ignore_case).into_iter()
+                .map(move |(line, text)| (path.clone(), line, text))
+        })
+        .collect();
+    found.par_sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    return found.into_iter()
+        .map(|(path, line, text)| Arc::new(EntryPath::labelled(format!("{}:{}: {}", path, line, text), path.as_path())))
+        .collect();
+}
+
+/// The file and line a grep result points at, None for anything that is not a grep result.
+pub fn parse_hit(hit: &EntryPath) -> Option<(&Path, usize)> {
+    if !hit.is_label() {
+        return None;
+    }
+    let path = hit.as_path();
+    let rest = hit.strip_prefix(escape(path.as_os_str()).as_ref())?.strip_prefix(':')?;
+    let digits = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+    let line = rest[..digits].parse::<usize>().ok()?;
+    return Some((path, line));
+}
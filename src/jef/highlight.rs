@@ -0,0 +1,148 @@
+/*
+ * Shows why a result matched. Every listing the search threads publish comes with the Highlight
+ * of the search that produced it, and the lists ask it for the matched byte ranges of the rows
+ * they are about to draw: the fuzzy alignment, the name prefix in `/`, the exact and anchored
+ * terms, the regex groups, the grep term in a grep hit. Like the search, they only look at the
+ * path below the root of its tree. Only rows near the selection are ever asked, so a search with
+ * half a million results costs no more to draw than one with ten.
+ *
+ * Rows wider than the list are cut around the match instead of at the end, with an ellipsis
+ * wherever something was left out. Without a match the end of the path stays, that is the name.
+ * A char counts as one column, near enough for file names.
+ */
+
+use std::ops::Range;
+use crate::jef::{
+    fuzzy::{fuzzy_positions, typo_positions},
+    grep::parse_hit,
+    path::{escape, EntryPath},
+    query::PathPattern,
+    terms::Terms,
+    unicode::{find_folded, fold, fold_spans},
+};
+
+#[derive(Debug, Default)]
+pub enum Highlight {
+    #[default]
+    Nothing,
+    // `f`, plain terms fuzzy and forgiving typos
+    Fuzzy { terms: Terms, max_typos: usize, ignore_diacritics: bool },
+    // `/`, plain terms a name prefix
+    NamePrefix { terms: Terms, ignore_diacritics: bool },
+    // already folded
+    Infix { search: String, ignore_diacritics: bool },
+    Pattern(PathPattern),
+    Grep(String),
+}
+
+impl Highlight {
+    /// The matched parts of `path` from byte `from` on, the part below its root, as sorted byte
+    /// ranges of that part that never overlap.
+    pub fn ranges(&self, hit: &EntryPath, from: usize) -> Vec<Range<usize>> {
+        let path = &hit[from..];
+        let mut ranges = match self {
+            Highlight::Nothing => Vec::new(),
+            Highlight::Fuzzy { terms, max_typos, ignore_diacritics } => terms.positions(path, |path, term| {
+                fuzzy_positions(path, term, *ignore_diacritics).or_else(|| typo_positions(path, &term.chars().collect::<Vec<char>>(), *max_typos, *ignore_diacritics))
+            }),
+            Highlight::NamePrefix { terms, ignore_diacritics } => terms.positions(path, |path, term| {
+                let name = path.rfind('/').map_or(0, |i| i + 1);
+                let spans = fold_spans(&path[name..], *ignore_diacritics);
+                let end = spans.get(term.chars().count().checked_sub(1)?)?.2.end;
+                Some(std::iter::once(name..name + end).collect())
+            }),
+            Highlight::Infix { search, ignore_diacritics } => {
+                let offset = path.len() - path.strip_prefix("./").unwrap_or(path).len();
+                find_folded(&path[offset..], search, *ignore_diacritics)
+                    .map(|range| range.start + offset..range.end + offset)
+                    .into_iter()
+                    .collect()
+            },
+            Highlight::Pattern(pattern) => pattern.ranges(path),
+            // the snippet always comes after the path
+            Highlight::Grep(search) => grep_ranges(hit, search).into_iter()
+                .map(|range| range.start.saturating_sub(from)..range.end.saturating_sub(from))
+                .collect(),
+        };
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        return merged;
+    }
+}
+
+// every time the grep term shows up in the snippet of a `path:line: snippet` hit, with the same
+// smart case grep_files uses
+fn grep_ranges(hit: &EntryPath, search: &str) -> Vec<Range<usize>> {
+    let Some((path, line)) = parse_hit(hit) else {
+        return Vec::new();
+    };
+    let start = format!("{}:{}: ", escape(path.as_os_str()), line).len();
+    let Some(snippet) = hit.get(start..) else {
+        return Vec::new();
+    };
+    let ignore_case = !search.chars().any(|c| c.is_uppercase());
+    let folded = fold(search, false);
+    let mut ranges = Vec::new();
+    let mut from = 0;
+    while from < snippet.len() {
+        let found = match ignore_case {
+            true => find_folded(&snippet[from..], &folded, false),
+            false => snippet[from..].find(search).map(|at| at..at + search.len()),
+        };
+        let Some(found) = found.filter(|found| !found.is_empty()) else {
+            break;
+        };
+        ranges.push(start + from + found.start..start + from + found.end);
+        from += found.end;
+    }
+    return ranges;
+}
+
+/// Cuts `text` down to `width` chars around `ranges`, split into runs that are or are not part of a
+/// match, in order.
+pub fn fit(text: &str, ranges: &[Range<usize>], width: usize) -> Vec<(String, bool)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let (mut start, mut end) = (0, chars.len());
+    if chars.len() > width && width > 2 {
+        // first and last matched char, or the very end
+        let char_at = |byte: usize| chars.partition_point(|(i, _c)| *i < byte);
+        let (first, last) = match (ranges.first(), ranges.last()) {
+            (Some(first), Some(last)) => (char_at(first.start), char_at(last.end)),
+            _ => (chars.len(), chars.len()),
+        };
+        if chars.len() - (width - 1) <= first {
+            start = chars.len() - (width - 1);
+        } else {
+            // centred on the match with room for an ellipsis either side, the match's start
+            // wins when it is too long to show whole
+            let room = width - 2;
+            start = first.saturating_sub(room.saturating_sub(last - first) / 2);
+            if start == 0 {
+                end = width - 1;
+            } else {
+                end = start + room;
+            }
+        }
+    }
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    let mut push = |c: char, matched: bool| match runs.last_mut() {
+        Some((run, was)) if *was == matched => run.push(c),
+        _ => runs.push((c.to_string(), matched)),
+    };
+    if start > 0 {
+        push('…', false);
+    }
+    for (i, c) in &chars[start..end] {
+        push(*c, ranges.iter().any(|range| range.contains(i)));
+    }
+    if end < chars.len() {
+        push('…', false);
+    }
+    return runs;
+}
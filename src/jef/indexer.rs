@@ -10,27 +10,125 @@
  */
 
 use std::{
-    time::Duration,
-    collections::HashMap,
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+    },
     thread,
 };
-use rayon::iter::{
-    IntoParallelRefIterator,
-    ParallelIterator
+use arc_swap::ArcSwap;
+use crossbeam_channel::{Receiver, Sender};
+use rayon::{
+    iter::{
+        IntoParallelIterator,
+        IntoParallelRefIterator,
+        ParallelIterator
+    },
+    slice::ParallelSliceMut,
 };
-use packed_simd::u8x16;
 use jwalk::{
     WalkDir,
     DirEntry
 };
-use crate::jef::flags::Flag;
+use crate::jef::{
+    cache::{self, CachedEntry},
+    filter::IndexFilter,
+    control::{Control, Inbox, Wake},
+    flags::SearchMode,
+    opener::{Config, IndexerConfig, SearchConfig, SortKey},
+    fuzzy::{fuzzy_score, typo_score},
+    highlight::Highlight,
+    frecency::{self, SharedFrecency},
+    grep::{grep_files, parse_hit},
+    meta::{split_filters, matches_all, EntryMeta, MetaFilter},
+    path::{escape, EntryPath},
+    query::{parse_pattern, PathPattern, QueryError},
+    roots::{project_root, root_path, RootScope},
+    terms::Terms,
+    simd,
+    snapshot::{Entry, IndexBuilder, Root, Segment, SharedIndex, SharedScope, Snapshot},
+    unicode::fold,
+    watcher::{Watcher, WatchEvent},
+};
+
+
+
+/// What one of the lists in the UI shows. The search thread replaces it a batch at a time as a
+/// search goes, every batch tagged with the search it came from.
+#[derive(Debug, Default)]
+pub struct Listing {
+    // bumped by every search the finder runs, the browser leaves it at 0
+    pub generation: u64,
+    pub paths: Vec<Arc<EntryPath>>,
+    // how the search matched, for drawing what it matched
+    pub highlight: Arc<Highlight>,
+    // results past the end of paths, the finder asks for them a page at a time
+    pub more: usize,
+    // the roots of the trees searched, the list shows paths from below them
+    pub roots: Vec<Arc<Root>>,
+    // how many of the snapshot's segments the latest search has been through, out of total
+    pub scanned: usize,
+    pub total: usize,
+}
 
+impl Listing {
+    pub fn is_searching(&self) -> bool {
+        return self.scanned < self.total;
+    }
 
+    /// The root of the tree `path` came from, the deepest one when they overlap.
+    pub fn root_of(&self, path: &str) -> Option<&Root> {
+        return self.roots.iter()
+            .filter(|root| root.offset(path) > 0)
+            .max_by_key(|root| root.offset(path))
+            .map(|root| root.as_ref());
+    }
 
-type SharedList = Arc<Mutex<Vec<Arc<String>>>>;
-type SearchTerm = Arc<Mutex<String>>;
+    /// Where `path` goes on below the root of the tree it came from.
+    pub fn offset(&self, path: &str) -> usize {
+        return self.root_of(path).map_or(0, |root| root.offset(path));
+    }
+}
+
+pub type SharedList = Arc<Mutex<Listing>>;
+
+/// How many results the finder list holds at a time. However many a search finds, the list only
+/// grows a page at a time as it is scrolled towards the end.
+pub const PAGE_SIZE: usize = 1000;
+
+// everything the latest search found, of which the list holds the first `shown`
+#[derive(Default)]
+struct Results {
+    paths: Vec<Arc<EntryPath>>,
+    shown: usize,
+}
+
+/// How far the index thread has got with the current directory, for the status line and :index.
+#[derive(Debug, Clone, Default)]
+pub struct IndexProgress {
+    pub entries: usize,
+    // directories the walk has found and not read yet
+    pub pending_dirs: usize,
+    pub started: Option<Instant>,
+    // set once the index is complete
+    pub took: Option<Duration>,
+}
+
+impl IndexProgress {
+    pub fn is_done(&self) -> bool {
+        return self.took.is_some();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        return self.took.unwrap_or_else(|| self.started.map(|started| started.elapsed()).unwrap_or_default());
+    }
+}
+
+pub type SharedProgress = Arc<Mutex<IndexProgress>>;
 
 macro_rules! hash_it {
     (|$s:expr, $num_c:ident, $rolling_hash:ident | $custom_code:block ) => {
@@ -38,11 +136,12 @@ macro_rules! hash_it {
         $rolling_hash = 2;
         $num_c = 0;
         let mut last_c: u16 = 0;
-        let s = $s.to_lowercase();
+        // always without diacritics, so the buckets hold every candidate either setting can match
+        let s = fold($s, true);
 
         for c in s.chars() {
             $num_c = (c as u16) << 8;
-            $num_c = $num_c | last_c;
+            $num_c |= last_c;
             $rolling_hash += $num_c as u32;
             $rolling_hash %= i;
             $custom_code
@@ -51,19 +150,6 @@ macro_rules! hash_it {
         }
     };
 }
-macro_rules! halting_loop {
-    (|$var: ident| $custom_code: block) => {
-        loop {
-            lock_readonly!(|$var|{
-                if matches!(*$var, Flag::Halt) {
-                    break;
-                }                    
-            });
-            $custom_code
-            thread::sleep(Duration::from_millis(100));
-        }
-    };
-}
 macro_rules! lock_as_mut {
     (|$var:ident | $custom_code: block) => {
         let $var = $var.clone();
@@ -81,95 +167,95 @@ macro_rules! lock_readonly {
     };
 }
 
-macro_rules! check_env {
-    (|$prev_dir:ident| $custom_code: block) => {
-        if let Ok(current_dir) = std::env::current_dir() {
-            if current_dir != $prev_dir {
-                $custom_code
-                $prev_dir = current_dir;
-            }
-        }
-    };
-}
-macro_rules! check_env_search {
-    (|$prev_dir:ident, $prev_search_str:ident, $current_search:ident| $custom_code: block) => {
-        if let Ok(current_dir) = std::env::current_dir() {
-            if current_dir != $prev_dir || $current_search != $prev_search_str{
-                $custom_code
-                $prev_dir = current_dir;
-                $prev_search_str = $current_search;
-            }
-        }
-        
-    };
-}
-
-pub fn init_browser(flag: Arc<Mutex<Flag>>, search: SearchTerm) -> (thread::JoinHandle<()>, SharedList) {
-    let shared_paths: SharedList = Arc::new(Mutex::new(Vec::new()));
+pub fn init_browser(inbox: Inbox, query_error: QueryError) -> (thread::JoinHandle<()>, SharedList) {
+    let shared_paths: SharedList = Arc::new(Mutex::new(Listing::default()));
     let thread_paths = shared_paths.clone();
 
     let browse_thread = thread::spawn(move || {
-        run_browser_thread(flag, thread_paths, search);
+        run_browser_thread(inbox, thread_paths, query_error);
     });
 
     return (browse_thread, shared_paths);
 }
 
 
-fn run_browser_thread(flag: Arc<Mutex<Flag>>,
+fn run_browser_thread(mut inbox: Inbox,
                       thread_paths: SharedList,
-                      search: SearchTerm){
-    let mut prev_dir = std::env::current_dir().unwrap_or_default();
-    let mut prev_search_str = String::new();
+                      query_error: QueryError){
+    let Config { indexer: config, search: search_config, .. } = Config::default_config();
+    let mut dir = std::env::current_dir().unwrap_or_default();
+    let mut search = String::new();
+    list_dir(&IndexFilter::new(&dir, false, &config), &search, search_config.ignore_diacritics, &thread_paths, &query_error);
+    loop {
+        let mut changed = false;
+        for message in inbox.wait() {
+            match message {
+                Control::Halt => return,
+                Control::SearchChanged { term, .. } if term != search => {
+                    search = term;
+                    changed = true;
+                },
+                Control::DirChanged(new_dir) => {
+                    dir = new_dir;
+                    changed = true;
+                },
+                _ => {},
+            }
+        }
+        if changed {
+            list_dir(&IndexFilter::new(&dir, false, &config), &search, search_config.ignore_diacritics, &thread_paths, &query_error);
+        }
+    }
+}
+
+fn list_dir(filter: &IndexFilter, search: &str, ignore_diacritics: bool, thread_paths: &SharedList, query_error: &QueryError) {
+    let pattern = parse_pattern(search, SearchMode::Fuzzy);
+    lock_as_mut!(|query_error|{
+        *query_error = pattern.as_ref().err().cloned();
+    });
+    let pattern = pattern.unwrap_or(None);
+    let terms = Terms::parse(search, ignore_diacritics);
+    let mut listed = Vec::new();
     for entry in WalkDir::new(".").min_depth(1).max_depth(1).sort(true) {
-        lock_readonly!(|flag|{
-            if matches!(*flag, Flag::Halt) {
-                break;
-            }                    
-        });
-        if let Some((path, _file_name, _depth)) = get_file_and_path(entry) {
-            lock_as_mut!(|thread_paths|{
-                thread_paths.push(path);
-            });
+        if !in_scope(filter, &entry) {
+            continue;
+        }
+        let Some((path, file_name, _depth)) = get_file_and_path(entry) else {
+            continue;
+        };
+        let keep = match &pattern {
+            Some(pattern) => pattern.is_match(&path),
+            None if terms.is_empty() => true,
+            // plain terms match the start of the name here
+            None => {
+                let name = fold(&file_name, ignore_diacritics);
+                terms.score(&path, |_path, term| name.starts_with(term).then_some(0)).is_some()
+            },
+        };
+        if keep {
+            listed.push(path);
         }
     }
-    halting_loop!(|flag|{
-        let mut current_search = String::new();
-        lock_readonly!(|search|{
-            current_search = search.clone().to_lowercase();
-        });
-        check_env_search!(|prev_dir, prev_search_str, current_search|{
-            lock_as_mut!(|thread_paths|{
-                thread_paths.clear();
-            });
-            for entry in WalkDir::new(".").min_depth(1).max_depth(1).sort(true) {
-                lock_readonly!(|flag|{
-                    if matches!(*flag, Flag::Halt) {
-                        break;
-                    }                    
-                });
-                if current_search == ""{
-                    if let Some((path, _file_name, _depth)) = get_file_and_path(entry) {
-                        lock_as_mut!(|thread_paths|{
-                            thread_paths.push(path);
-                        });
-                    }
-                } else {
-                    if let Some((path, file_name, _depth)) = get_file_and_path(entry) {
-                        let file_name = file_name.to_lowercase();
-                        if file_name.starts_with(&current_search) {
-                            lock_as_mut!(|thread_paths|{
-                                thread_paths.push(path);
-                            });
-                        }
-                    }
-                }
-            }
-        });
+    let highlight = match pattern {
+        Some(pattern) => Highlight::Pattern(pattern),
+        None => Highlight::NamePrefix { terms, ignore_diacritics },
+    };
+    lock_as_mut!(|thread_paths|{
+        thread_paths.paths = listed;
+        thread_paths.highlight = Arc::new(highlight);
     });
 }
 
-fn get_file_and_path<C: jwalk::ClientState>(entry: Result<DirEntry<C>, jwalk::Error>) -> Option<(Arc<String>, Arc<String>, u16)> {
+// the browser lists everything in the directory except what the exclude/include globs rule out
+fn in_scope<C: jwalk::ClientState>(filter: &IndexFilter, entry: &Result<DirEntry<C>, jwalk::Error>) -> bool {
+    let Ok(entry) = entry else {
+        return false;
+    };
+    return !filter.is_excluded(&entry.path(), entry.file_type().is_dir());
+}
+
+// names that are not valid UTF-8 are hashed and matched by their escaped text, see path.rs
+fn get_file_and_path<C: jwalk::ClientState>(entry: Result<DirEntry<C>, jwalk::Error>) -> Option<(Arc<EntryPath>, Arc<String>, u16)> {
 
     if entry.is_err() {
         return None;
@@ -181,20 +267,8 @@ fn get_file_and_path<C: jwalk::ClientState>(entry: Result<DirEntry<C>, jwalk::Er
     
 
     if let Some(file_name) = path.file_name() {
-        let path_str: Arc<String>;
-        let file_name_str: Arc<String>;
-        if let Some(path) = path.to_str() {
-            path_str = Arc::new(path.to_string());
-        }else{
-            return None;
-        }
-
-        if let Some(file_name) = file_name.to_str() {
-            file_name_str = Arc::new(file_name.to_string());
-        }else{
-            return None;
-        }
-
+        let path_str = Arc::new(EntryPath::new(&path));
+        let file_name_str = Arc::new(escape(file_name).into_owned());
         return Some((path_str, file_name_str, depth));
     } else {
         return None;
@@ -203,187 +277,790 @@ fn get_file_and_path<C: jwalk::ClientState>(entry: Result<DirEntry<C>, jwalk::Er
 }
 
 
-#[derive(Debug)]
-pub struct FileMap {
-    map: HashMap<u64, SharedList>,
-    stack: u16,
-    done_indexing: bool,
+pub fn init_indexer(inbox: Inbox, root: &str, show_ignored: Arc<Mutex<bool>>, search: Sender<Control>) -> (thread::JoinHandle<()>, SharedScope, SharedProgress) {
+    let shared_scope: SharedScope = Arc::new(ArcSwap::from_pointee(Vec::new()));
+    let shared_progress: SharedProgress = Arc::new(Mutex::new(IndexProgress::default()));
+
+    let root = root.to_string().clone();
+    let outlet = Outlet {
+        scope: shared_scope.clone(),
+        progress: shared_progress.clone(),
+        search,
+    };
+    let indexer_thread = thread::spawn(move || {
+        run_index_thread(inbox, outlet, &root, show_ignored)
+    });
+
+    return (indexer_thread, shared_scope, shared_progress);
 }
-type SharedFileMap = Arc<Mutex<FileMap>>;
 
-impl FileMap {
-    fn new() -> FileMap {
-        FileMap {
-            map: HashMap::new(),
-            stack: 0,
-            done_indexing: false,
-        }
+// one tree the index thread keeps up to date, the current directory, its project or a named root
+struct Tree {
+    // where the walk starts, "." for the current directory and the absolute path for a root
+    walk_root: PathBuf,
+    // kept for as long as the current directory stays inside it
+    project: bool,
+    cache_key: PathBuf,
+    filter: IndexFilter,
+    // only this thread ever writes the index, everyone else reads whatever was published last
+    builder: IndexBuilder,
+    index: SharedIndex,
+    cached: Vec<CachedEntry>,
+    watcher: Option<Watcher>,
+    // false until a walk or the cache got all of it in
+    built: bool,
+    // the next build walks even when there is a cache, for `:index`
+    force_walk: bool,
+}
+
+impl Tree {
+    fn new(walk_root: &Path, base: &Path, respect_ignore: bool, config: &IndexerConfig) -> Tree {
+        return Tree::rooted(Root::new(walk_root), walk_root, base, respect_ignore, config);
+    }
+
+    fn rooted(root: Root, walk_root: &Path, base: &Path, respect_ignore: bool, config: &IndexerConfig) -> Tree {
+        return Tree {
+            walk_root: walk_root.to_path_buf(),
+            project: false,
+            cache_key: base.join(walk_root),
+            filter: IndexFilter::new(base, respect_ignore, config),
+            builder: IndexBuilder::new().rooted(root),
+            index: Arc::new(ArcSwap::from_pointee(Snapshot::default())),
+            cached: Vec::new(),
+            watcher: None,
+            built: false,
+            force_walk: false,
+        };
+    }
+
+    fn project(walk_root: &Path, respect_ignore: bool, config: &IndexerConfig) -> Tree {
+        return Tree { project: true, ..Tree::new(walk_root, walk_root, respect_ignore, config) };
+    }
+
+    // whether moving to `dir` leaves this tree out of date or out of scope
+    fn outgrown_by(&self, dir: &Path) -> bool {
+        return self.walk_root.is_relative() || (self.project && !dir.starts_with(&self.walk_root));
     }
 }
 
-pub fn init_indexer(flag: Arc<Mutex<Flag>>, root: &str) -> (thread::JoinHandle<()>, SharedFileMap) {
-    let shared_file_map: SharedFileMap = Arc::new(Mutex::new(FileMap::new()));
+// where the index thread's work shows up: the trees searches cover, the status line and a nudge
+// for the search thread whenever there is something new to search
+struct Outlet {
+    scope: SharedScope,
+    progress: SharedProgress,
+    search: Sender<Control>,
+}
 
-    let root = root.to_string().clone();
-    let thread_map = shared_file_map.clone();
-    let indexer_thread = thread::spawn(move || {
-        run_index_thread(flag, thread_map, &root)
-    });
+impl Outlet {
+    fn publish(&self, tree: &mut Tree) {
+        if tree.builder.publish(&tree.index) {
+            let _ = self.search.send(Control::IndexChanged);
+        }
+        let entries: usize = self.scope.load().iter().map(|index| index.load().entry_count()).sum();
+        let progress = &self.progress;
+        lock_as_mut!(|progress|{
+            progress.entries = entries;
+        });
+    }
+
+    fn rescope(&self, trees: Vec<SharedIndex>) {
+        self.scope.store(Arc::new(trees));
+        let _ = self.search.send(Control::IndexChanged);
+    }
+
+    fn started(&self) {
+        let progress = &self.progress;
+        lock_as_mut!(|progress|{
+            *progress = IndexProgress { started: Some(Instant::now()), ..IndexProgress::default() };
+        });
+    }
+
+    fn pending_dirs(&self, pending_dirs: usize) {
+        let progress = &self.progress;
+        lock_as_mut!(|progress|{
+            progress.pending_dirs = pending_dirs;
+        });
+    }
 
-    return (indexer_thread, shared_file_map);
+    fn finished(&self) {
+        let progress = &self.progress;
+        lock_as_mut!(|progress|{
+            progress.pending_dirs = 0;
+            progress.took = Some(progress.elapsed());
+        });
+    }
 }
 
-fn run_index_thread(flag: Arc<Mutex<Flag>>,
-                    thread_map: Arc<Mutex<FileMap>>,
-                    root: &str){
-    let mut prev_dir = PathBuf::default();
-    halting_loop!(|flag|{
-        check_env!(|prev_dir|{
-            lock_as_mut!(|thread_map|{
-                thread_map.map.clear();
-                thread_map.map.shrink_to(0);
+fn run_index_thread(mut inbox: Inbox,
+                    outlet: Outlet,
+                    root: &str,
+                    show_ignored: Arc<Mutex<bool>>){
+    let config = Config::default_config();
+    let mut current_dir = std::env::current_dir().unwrap_or_default();
+    let mut scope = RootScope::Project;
+    // the current directory's tree, dropped whenever the directory changes
+    let mut here: Option<Tree> = None;
+    // the project around the current directory, its top included, kept while moving around
+    // inside it
+    let mut project: Option<Tree> = None;
+    let project_around = |dir: &Path| project_root(dir, &config.indexer.project_markers);
+    // the project tree searches cover right now, None while that is `here`
+    let mut in_project: Option<PathBuf> = None;
+    // named roots stay once they are built, so switching back to one is instant
+    let mut roots: HashMap<String, Tree> = HashMap::new();
+    let mut rescope = true;
+    let mut pending: Vec<Control> = Vec::new();
+    loop {
+        for message in std::mem::take(&mut pending) {
+            match message {
+                Control::Halt => return,
+                Control::DirChanged(dir) => {
+                    current_dir = dir;
+                    here = None;
+                    rescope |= match scope {
+                        RootScope::CurrentDir => true,
+                        RootScope::Project => in_project.is_none() || project_around(&current_dir) != in_project,
+                        _ => false,
+                    };
+                },
+                Control::Reindex => {
+                    // the trees stay where they are and just get walked again, roots out of
+                    // scope included once they are picked
+                    for tree in here.iter_mut().chain(project.iter_mut()).chain(roots.values_mut()) {
+                        tree.built = false;
+                        tree.force_walk = true;
+                    }
+                    rescope = true;
+                },
+                Control::FilterChanged => {
+                    here = None;
+                    project = None;
+                    roots.clear();
+                    rescope = true;
+                },
+                Control::RootChanged(new_scope) => {
+                    scope = new_scope;
+                    rescope = true;
+                },
+                _ => {},
+            }
+        }
+        if rescope {
+            rescope = false;
+            let mut respect_ignore = config.indexer.respect_ignore;
+            lock_readonly!(|show_ignored|{
+                respect_ignore &= !*show_ignored;
             });
-            index_directories(flag.clone(), &root, thread_map.clone());
-        });
-    });
+            let names = scope.names(&config.roots);
+            in_project = match scope {
+                RootScope::Project => project_around(&current_dir),
+                _ => None,
+            };
+            let mut active: Vec<&mut Tree> = match (&scope, &in_project) {
+                (_, Some(walk_root)) => {
+                    if project.as_ref().is_none_or(|tree| tree.walk_root != *walk_root) {
+                        project = Some(Tree::project(walk_root, respect_ignore, &config.indexer));
+                    }
+                    project.iter_mut().collect()
+                },
+                (RootScope::Project | RootScope::CurrentDir, None) => {
+                    vec![here.get_or_insert_with(|| Tree::new(Path::new(root), &current_dir, respect_ignore, &config.indexer))]
+                },
+                _ => {
+                    for name in &names {
+                        roots.entry(name.to_string()).or_insert_with(|| {
+                            let path = root_path(&config.roots[*name]);
+                            Tree::rooted(Root::new(&path).labelled(name), &path, &path, respect_ignore, &config.indexer)
+                        });
+                    }
+                    roots.iter_mut().filter(|(name, _tree)| names.contains(&name.as_str())).map(|(_name, tree)| tree).collect()
+                },
+            };
+            // searches see every tree in scope right away, the unbuilt ones fill in as they go
+            outlet.rescope(active.iter().map(|tree| tree.index.clone()).collect());
+            outlet.started();
+            let mut complete = true;
+            for tree in active.iter_mut().filter(|tree| !tree.built) {
+                if !build(tree, &mut inbox, &outlet, &mut pending) {
+                    complete = false;
+                    break;
+                }
+            }
+            if complete {
+                outlet.finished();
+            }
+        }
+        if !pending.is_empty() {
+            continue;
+        }
+        let mut watched: Vec<&mut Tree> = here.iter_mut().chain(project.iter_mut()).chain(roots.values_mut())
+            .filter(|tree| tree.watcher.is_some())
+            .collect();
+        let wakeups: Vec<Receiver<()>> = watched.iter().flat_map(|tree| &tree.watcher).map(|watcher| watcher.wakeups().clone()).collect();
+        let wakeups: Vec<&Receiver<()>> = wakeups.iter().collect();
+        match inbox.wait_with(&wakeups) {
+            Wake::Control(messages) => pending = messages,
+            Wake::Other((woken, ())) => index_events(watched.swap_remove(woken), &outlet),
+        }
+    }
 }
 
+// indexes the tree from the cache or a walk, false if a message came in that makes finishing moot
+fn build(tree: &mut Tree, inbox: &mut Inbox, outlet: &Outlet, pending: &mut Vec<Control>) -> bool {
+    tree.watcher = None;
+    tree.builder.clear();
+    outlet.publish(tree);
+    let cached = match tree.force_walk {
+        true => None,
+        false => cache::load(&tree.cache_key, &tree.filter),
+    };
+    if let Some(loaded) = cached {
+        // show the cached tree right away, then patch whatever moved since it was saved
+        index_cached(&mut tree.builder, &loaded);
+        outlet.publish(tree);
+        let (loaded, changed) = cache::refresh(loaded, &tree.filter);
+        if changed {
+            tree.builder.clear();
+            index_cached(&mut tree.builder, &loaded);
+            outlet.publish(tree);
+            cache::save(&tree.cache_key, &tree.filter, &loaded);
+        }
+        tree.cached = loaded;
+    } else {
+        let Some(walked) = index_directories(tree, inbox, outlet, pending) else {
+            // half a walk is not worth caching or watching, the messages say what is next
+            tree.cached = Vec::new();
+            return false;
+        };
+        tree.cached = walked;
+        tree.force_walk = false;
+        cache::save(&tree.cache_key, &tree.filter, &tree.cached);
+    }
+    tree.watcher = watch_tree(&tree.cached);
+    tree.built = true;
+    return true;
+}
+
+// applies whatever the tree's watcher has queued up
+fn index_events(tree: &mut Tree, outlet: &Outlet) {
+    let Some(watcher) = tree.watcher.as_mut() else {
+        return;
+    };
+    let mut overflowed = false;
+    for event in watcher.poll() {
+        match event {
+            WatchEvent::Created { path, stack, is_dir } => {
+                index_created(&mut tree.builder, watcher, &tree.filter, path, stack, is_dir);
+            },
+            WatchEvent::Removed { path, stack: _ } => tree.builder.remove_tree(&path),
+            WatchEvent::Modified { path, stack } => {
+                let meta = EntryMeta::of(path.as_path(), tree.filter.follows_symlinks());
+                tree.builder.update_meta(&path, stack, meta);
+            },
+            WatchEvent::Overflow => overflowed = true,
+        }
+    }
+    // the whole batch of events goes out as one snapshot
+    outlet.publish(tree);
+    if overflowed {
+        // events were dropped, so let the directory mtimes say which subtrees need a re-walk
+        let (refreshed, _changed) = cache::refresh(std::mem::take(&mut tree.cached), &tree.filter);
+        tree.cached = refreshed;
+        tree.builder.clear();
+        index_cached(&mut tree.builder, &tree.cached);
+        outlet.publish(tree);
+        cache::save(&tree.cache_key, &tree.filter, &tree.cached);
+        tree.watcher = watch_tree(&tree.cached);
+    }
+}
+
+fn watch_tree(cached: &[CachedEntry]) -> Option<Watcher> {
+    let mut watcher = Watcher::new()?;
+    for entry in cached {
+        if entry.dir_mtime.is_some() && !watcher.watch(&entry.path, entry.depth) {
+            break;
+        }
+    }
+    return Some(watcher);
+}
+
+fn index_created(builder: &mut IndexBuilder, watcher: &mut Watcher, filter: &IndexFilter, path: EntryPath, stack: u16, is_dir: bool) {
+    if let Some(parent) = path.as_path().parent() {
+        let rules = filter.rules_for(parent);
+        if filter.skips(&rules, path.as_path(), is_dir, stack) {
+            return;
+        }
+    }
+    // watch before walking so nothing created in between is missed, contains() sorts out doubles
+    let meta = EntryMeta::of(path.as_path(), filter.follows_symlinks());
+    let mut created = vec![CachedEntry { path: path.clone(), depth: stack, dir_mtime: None, meta }];
+    if is_dir && filter.can_descend(path.as_path()) {
+        watcher.watch(&path, stack);
+        created.extend(cache::walk_subtree(filter, path.as_path(), stack));
+    }
+    for entry in created {
+        if entry.dir_mtime.is_some() {
+            watcher.watch(&entry.path, entry.depth);
+        }
+        if !builder.contains(&entry.path, entry.depth) {
+            builder.push(&entry.path, entry.depth, entry.meta);
+        }
+    }
+}
 
 
-pub fn init_index_search(flag: Arc<Mutex<Flag>>, 
-                         shared_file_map: SharedFileMap, 
-                         search: Arc<Mutex<String>>) -> (thread::JoinHandle<()>, SharedList) {
-    let shared_paths: SharedList = Arc::new(Mutex::new(Vec::new()));
-    let shared_file_map = shared_file_map.clone();
 
-    let thread_map = shared_file_map.clone();
+pub fn init_index_search(inbox: Inbox,
+                         shared_scope: SharedScope, 
+                         query_error: QueryError,
+                         frecency: SharedFrecency) -> (thread::JoinHandle<()>, SharedList) {
+    let shared_paths: SharedList = Arc::new(Mutex::new(Listing::default()));
+
     let thread_paths = shared_paths.clone();
 
     let search_thread = thread::spawn(move ||{
-        run_search_thread(flag, search, shared_file_map, thread_paths, thread_map);
+        run_search_thread(inbox, query_error, frecency, shared_scope, thread_paths);
     });
     return (search_thread, shared_paths);
 }
 
-fn run_search_thread(flag: Arc<Mutex<Flag>>,
-                     search: Arc<Mutex<String>>,
-                     shared_file_map: SharedFileMap,
-                     thread_paths: Arc<Mutex<Vec<Arc<String>>>>,
-                     thread_map: Arc<Mutex<FileMap>>){
-    let mut last_search = String::new();
-    let mut last_size:usize = 0;
-    halting_loop!(|flag|{
-        lock_readonly!(|search|{
-            let mut stack: u16 = 0;
-            let mut size: usize = 0;
-            lock_readonly!(|shared_file_map|{
-                stack = shared_file_map.stack;
-                size = shared_file_map.map.len();
-            });
-            if *search != last_search || size != last_size{
-                lock_as_mut!(|thread_paths|{
-                    thread_paths.clear();
-                });
-                last_search = search.clone();
-                last_size = size;
-                let hashes = get_possible_hashes(stack, &search);
-                for hash in hashes {
-                    check_index(thread_map.clone(), thread_paths.clone(), &hash, &search);
-                }
+fn run_search_thread(mut inbox: Inbox,
+                     query_error: QueryError,
+                     frecency: SharedFrecency,
+                     shared_scope: SharedScope,
+                     thread_paths: SharedList){
+    let mut current_search = String::new();
+    let mut current_mode = SearchMode::Fuzzy;
+    let mut last_index: Vec<(usize, u64)> = Vec::new();
+    let mut generation: u64 = 0;
+    // whatever came in while the last search ran
+    let mut held: Vec<Control> = Vec::new();
+    let mut results = Results::default();
+    let config = Config::default_config().search;
+    let SearchConfig { ignore_diacritics, max_typos, .. } = config;
+    loop {
+        // however many keys or snapshots piled up while the last search ran, the next one runs once
+        let messages = match held.is_empty() {
+            true => inbox.wait(),
+            false => {
+                let mut messages = std::mem::take(&mut held);
+                messages.extend(inbox.check());
+                messages
+            },
+        };
+        let mut changed = false;
+        let mut paged = false;
+        for message in messages {
+            match message {
+                Control::Halt => return,
+                Control::SearchChanged { term, mode } => {
+                    changed |= term != current_search || mode != current_mode;
+                    current_search = term;
+                    current_mode = mode;
+                },
+                Control::MoreResults(wanted) => paged |= wanted == generation,
+                // checked against the generation below
+                _ => {},
             }
+        }
+        if paged && results.shown < results.paths.len() {
+            results.shown += PAGE_SIZE;
+            let (list, page) = (&thread_paths, &results);
+            lock_as_mut!(|list|{
+                list.more = page.paths.len().saturating_sub(page.shown);
+                list.paths = page.paths.iter().take(page.shown).cloned().collect();
+            });
+        }
+        // no lock, these snapshots stay exactly as they are for the whole search however much the
+        // indexer publishes meanwhile
+        let scope = shared_scope.load_full();
+        let snapshots: Vec<Arc<Snapshot>> = scope.iter().map(|index| index.load_full()).collect();
+        // which trees at which generation, so switching roots counts as a change too
+        let index: Vec<(usize, u64)> = scope.iter().zip(&snapshots)
+            .map(|(tree, snapshot)| (Arc::as_ptr(tree) as usize, snapshot.generation))
+            .collect();
+        if !changed && index == last_index {
+            continue;
+        }
+        last_index = index;
+        generation += 1;
+        // type:, size: and friends come off first, the name matchers get what is left
+        let query = split_filters(&current_search).and_then(|(filters, name_search)| {
+            let pattern = parse_pattern(&name_search, current_mode)?;
+            return Ok((filters, name_search, pattern));
         });
-    });
+        lock_as_mut!(|query_error|{
+            *query_error = query.as_ref().err().cloned();
+        });
+        let (filters, name_search, pattern) = match query {
+            Ok(query) => query,
+            // a query that does not compile finds nothing, the error shows next to it instead
+            Err(_) => {
+                let scan = Scan::start(&mut inbox, &thread_paths, (&current_search, current_mode), generation, &snapshots, Highlight::Nothing, &mut results);
+                held = scan.finish(Vec::<((), _)>::new());
+                continue;
+            }
+        };
+        let terms = Terms::parse(&name_search, ignore_diacritics);
+        let highlight = match (&pattern, current_mode) {
+            (Some(pattern), _) => Highlight::Pattern(pattern.clone()),
+            (None, SearchMode::Fuzzy) => Highlight::Fuzzy { terms: terms.clone(), max_typos, ignore_diacritics },
+            (None, SearchMode::Grep) => Highlight::Grep(name_search.clone()),
+            (None, _) => Highlight::Infix { search: fold(&name_search, ignore_diacritics).into_owned(), ignore_diacritics },
+        };
+        let scan = Scan::start(&mut inbox, &thread_paths, (&current_search, current_mode), generation, &snapshots, highlight, &mut results);
+        held = match (pattern, current_mode) {
+            (Some(pattern), _) => pattern_search(scan, &snapshots, &pattern, &filters),
+            (None, _) if name_search.is_empty() && !filters.is_empty() => {
+                filter_search(scan, &snapshots, &filters)
+            },
+            (None, SearchMode::Fuzzy) => {
+                let mut scores = HashMap::new();
+                lock_readonly!(|frecency|{
+                    // relative paths for the current directory, absolute ones for named roots
+                    scores = frecency.scores_under(&std::env::current_dir().unwrap_or_default(), frecency::now());
+                    scores.extend(frecency.scores(frecency::now()));
+                });
+                fuzzy_rank(scan, &snapshots, &terms, &filters, &scores, &config)
+            },
+            (None, SearchMode::Grep) => grep_search(scan, &snapshots, &name_search, &filters),
+            (None, _) => infix_search(scan, &snapshots, &name_search, &filters, ignore_diacritics),
+        };
+    }
 }
 
+// how often a search in progress shows what it has found so far
+const STREAM_INTERVAL: Duration = Duration::from_millis(50);
 
-fn check_index(shared_file_map: SharedFileMap, shared_paths: SharedList, hash: &u64, search: &str) {
-    lock_readonly!(|shared_file_map|{
-        if let Some(str_ptr) = shared_file_map.map.get(&hash){
-            lock_readonly!(|str_ptr|{
-                str_ptr.par_iter().for_each(|dir| {
-                    if starts_with_prefix_simd(last_chars_until_forward_slash(&dir),search) {
-                        shared_paths.lock().unwrap().push(dir.clone());
-                    }
-                });
-            });
-        }
-    });
+// one search working through the snapshots a segment at a time, so the list fills in as results
+// come and a newer query does not have to wait for this one to finish
+struct Scan<'a> {
+    inbox: &'a mut Inbox,
+    list: &'a SharedList,
+    query: (&'a str, SearchMode),
+    generation: u64,
+    highlight: Arc<Highlight>,
+    roots: Vec<Arc<Root>>,
+    results: &'a mut Results,
+    scanned: usize,
+    total: usize,
+    published: Option<Instant>,
+    held: Vec<Control>,
 }
 
-fn index_directories(flag: Arc<Mutex<Flag>>, root: &str, shared_file_map: SharedFileMap) { 
-    for entry in WalkDir::new(root).skip_hidden(false) {
-        lock_readonly!(|flag|{
-            if matches!(*flag, Flag::Halt) {
-                break;
+impl<'a> Scan<'a> {
+    // the list keeps showing the last results until this search has some of its own, only the
+    // progress switches over right away
+    #[allow(clippy::too_many_arguments)]
+    fn start(inbox: &'a mut Inbox, list: &'a SharedList, query: (&'a str, SearchMode), generation: u64, snapshots: &[Arc<Snapshot>], highlight: Highlight, results: &'a mut Results) -> Scan<'a> {
+        let total = snapshots.iter().map(|snapshot| snapshot.segments().len()).sum();
+        let shared = list;
+        lock_as_mut!(|shared|{
+            shared.scanned = 0;
+            shared.total = total;
+        });
+        *results = Results { paths: Vec::new(), shown: PAGE_SIZE };
+        let roots = snapshots.iter().map(|snapshot| snapshot.root.clone()).collect();
+        return Scan { inbox, list, query, generation, highlight: Arc::new(highlight), roots, results, scanned: 0, total, published: None, held: Vec::new() };
+    }
+
+    // false once a Halt or a different query has come in, the search just stops there
+    fn carry_on(&mut self) -> bool {
+        for message in self.inbox.check() {
+            match message {
+                // the next publish shows one more page
+                Control::MoreResults(wanted) if wanted == self.generation => self.results.shown += PAGE_SIZE,
+                message => self.held.push(message),
             }
+        }
+        let mut latest = self.query;
+        for message in &self.held {
+            match message {
+                Control::Halt => return false,
+                Control::SearchChanged { term, mode } => latest = (term, *mode),
+                _ => {},
+            }
+        }
+        return latest == self.query;
+    }
+
+    // only the pages the list has asked for, never the whole of what was found
+    fn publish<K>(&mut self, found: &[(K, Arc<EntryPath>)]) {
+        let (list, generation, scanned, total) = (self.list, self.generation, self.scanned, self.total);
+        let paths = found.iter().take(self.results.shown).map(|(_key, path)| path.clone()).collect();
+        let more = found.len().saturating_sub(self.results.shown);
+        let (highlight, roots) = (self.highlight.clone(), self.roots.clone());
+        lock_as_mut!(|list|{
+            *list = Listing { generation, paths, highlight, more, roots, scanned, total };
         });
-        if let Some((path_as_string, file_name, stack)) = get_file_and_path(entry) {
-            let shared_file_map = shared_file_map.clone();
-            index_single_dir(shared_file_map, path_as_string, file_name, stack);
+        self.published = Some(Instant::now());
+    }
+
+    fn finish<K>(mut self, found: Vec<(K, Arc<EntryPath>)>) -> Vec<Control> {
+        self.scanned = self.total;
+        self.publish(&found);
+        self.results.paths = found.into_iter().map(|(_key, path)| path).collect();
+        return self.held;
+    }
+
+    // runs `batch` over one segment after another, tree by tree, merging what each finds into a
+    // list ordered by key and then path on top of whatever `found` already holds. `batch` gets the
+    // position of the snapshot the segment belongs to. A path that turns up again, from roots that
+    // overlap say, is only listed the first time
+    fn ranked<K: Ord + Send>(mut self,
+                             snapshots: &[Arc<Snapshot>],
+                             mut found: Vec<(K, Arc<EntryPath>)>,
+                             batch: impl Fn(usize, &Snapshot, &Segment) -> Vec<(K, Arc<EntryPath>)>) -> Vec<Control> {
+        let order = |a: &(K, Arc<EntryPath>), b: &(K, Arc<EntryPath>)| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1));
+        let mut listed: HashSet<Arc<EntryPath>> = HashSet::new();
+        found.retain(|(_key, path)| listed.insert(path.clone()));
+        found.par_sort_unstable_by(order);
+        let segments = snapshots.iter().enumerate()
+            .flat_map(|(tree, snapshot)| snapshot.segments().iter().map(move |segment| (tree, snapshot, segment)));
+        for (tree, snapshot, segment) in segments {
+            let mut more = batch(tree, snapshot, segment);
+            more.retain(|(_key, path)| listed.insert(path.clone()));
+            more.par_sort_unstable_by(order);
+            // two sorted runs back to back, which the stable sort merges in one pass
+            found.append(&mut more);
+            found.sort_by(order);
+            self.scanned += 1;
+            if !self.carry_on() {
+                return self.held;
+            }
+            let due = self.published.is_none_or(|published| published.elapsed() >= STREAM_INTERVAL);
+            if due && self.scanned < self.total {
+                self.publish(&found);
+            }
         }
+        return self.finish(found);
     }
-    lock_readonly!(|shared_file_map|{
-        shared_file_map.done_indexing;
-    });
 }
 
-fn index_single_dir(file_map: SharedFileMap, path: Arc<String>, file_name: Arc<String>, stack: u16) {
-    lock_as_mut!(|file_map|{
-        if stack > file_map.stack {
-            file_map.stack = stack;
+// the index only keeps node ids, so each thread rebuilds path text into one reused buffer and only
+// the matches become EntryPaths
+fn scored<'a, K: Send>(snapshot: &'a Snapshot,
+                       entries: impl ParallelIterator<Item = &'a Entry>,
+                       key: impl Fn(&str, &Entry) -> Option<K> + Sync) -> Vec<(K, Arc<EntryPath>)> {
+    return entries
+        .map_init(String::new, |text, entry| {
+            snapshot.write_text(entry, text);
+            key(text, entry).map(|key| (key, snapshot.path(entry)))
+        })
+        .flatten()
+        .collect();
+}
+
+fn passes(path: &str, entry: &Entry, filters: &[MetaFilter]) -> bool {
+    return matches_all(filters, path, entry.meta.as_ref());
+}
+
+// one visit in the last hour is worth about one more matched character
+const FRECENCY_WEIGHT: f64 = 12.0;
+
+// scores every indexed path against the search, with a bump for whatever has been opened often or
+// lately, and orders them by the configured keys, best first by default. Names a typo or two off
+// score last. Prefix hits straight out of the hash buckets get scored before anything else, so the
+// likeliest results are on screen before the full pass has got far
+fn fuzzy_rank(scan: Scan, snapshots: &[Arc<Snapshot>], terms: &Terms, filters: &[MetaFilter], frecency: &HashMap<String, f64>, config: &SearchConfig) -> Vec<Control> {
+    let SearchConfig { ignore_diacritics, max_typos, .. } = *config;
+    // the typo pass takes the terms as chars, split here once instead of for every path
+    let typo_queries: HashMap<&str, Vec<char>> = terms.plain().map(|term| (term, term.chars().collect())).collect();
+    let score = |root: &Root, text: &str, entry: &Entry| {
+        let relative = root.relative(text);
+        if !passes(relative, entry, filters) {
+            return None;
         }
-        let hashset = get_hashset(stack, &*file_name);
-        for hash in hashset {
-            if let Some(list) = file_map.map.get(&hash) {
-                lock_as_mut!(|list|{
-                    list.push(path.clone());
-                });
-            }else{
-                let list: Vec<Arc<String>> = vec![path.clone()];
-                let list = Arc::new(Mutex::new(list));
-                file_map.map.insert(hash, list);
-            }
+        let score = terms.score(relative, |path, term| {
+            fuzzy_score(path, term, ignore_diacritics).or_else(|| typo_score(path, &typo_queries[term], max_typos, ignore_diacritics))
+        })?;
+        let boost = frecency.get(text).map_or(0.0, |frecency| frecency.ln_1p() * FRECENCY_WEIGHT);
+        return Some(rank(&config.sort, score + boost as i64, relative, entry));
+    };
+    // only the file name is hashed, so a slashed term looks up its last segment and the score
+    // checks the directories and the other terms
+    let name = last_chars_until_forward_slash(terms.lookup().unwrap_or_default());
+    let prefix = fold(name, ignore_diacritics);
+    // per tree, node ids are only unique within one
+    let mut seen: Vec<HashSet<u32>> = vec![HashSet::new(); snapshots.len()];
+    let mut first = Vec::new();
+    for (tree, snapshot) in snapshots.iter().enumerate().filter(|_| !name.is_empty()) {
+        let mut hits: Vec<Entry> = Vec::new();
+        for hash in get_possible_hashes(snapshot.stack, name) {
+            // the bucket hash collides plenty, the prefix check sorts those out
+            let found: Vec<Entry> = snapshot.bucket(hash)
+                .map_init(String::new, |text, entry| {
+                    snapshot.write_text(entry, text);
+                    let file_name = fold(last_chars_until_forward_slash(text), ignore_diacritics);
+                    starts_with_prefix_simd(&file_name, &prefix).then_some(*entry)
+                })
+                .flatten()
+                .collect();
+            hits.extend(found.into_iter().filter(|entry| seen[tree].insert(entry.node)));
         }
+        first.extend(scored(snapshot, hits.par_iter(), |text, entry| score(&snapshot.root, text, entry)));
+    }
+    return scan.ranked(snapshots, first, |tree, snapshot, segment| {
+        let entries = segment.entries().filter(|entry| !seen[tree].contains(&entry.node));
+        scored(snapshot, entries, |text, entry| score(&snapshot.root, text, entry))
     });
 }
 
+// one part of where a fuzzy result goes in the list, in the order of the configured keys
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Number(i64),
+    Text(String),
+}
 
-//indexes 48gb in under 4s on ssd. lol
+fn rank(sort: &[SortKey], score: i64, text: &str, entry: &Entry) -> Vec<Rank> {
+    return sort.iter()
+        .map(|key| match key {
+            SortKey::Score => Rank::Number(-score),
+            SortKey::Depth => Rank::Number(entry.depth as i64),
+            SortKey::Name => Rank::Text(fold(last_chars_until_forward_slash(text), true).into_owned()),
+            // anything without a known mtime after all that have one
+            SortKey::Mtime => Rank::Number(entry.meta.as_ref().map_or(i64::MAX, |meta| -(meta.mtime as i64))),
+        })
+        .collect();
+}
 
-pub fn last_chars_until_forward_slash(s: &str) -> &str {
-    let slash_byte = b'/';
-    let slash_simd = u8x16::splat(slash_byte);
-
-    let mut i = s.len() as isize - 16;
-    while i > 16 {
-        let chunk = &s.as_bytes()[i as usize..];
-        let chunk_simd = u8x16::from_slice_unaligned(chunk);
-
-        let cmp_result = chunk_simd.eq(slash_simd);
-        if cmp_result.any() {
-            let mask = cmp_result.bitmask();
-            let pos = mask.leading_zeros() as usize;
-            return std::str::from_utf8(&chunk[chunk.len()-pos..]).unwrap();
+// finds every path containing the search anywhere, narrowing candidates with the trigram index
+fn infix_search(scan: Scan, snapshots: &[Arc<Snapshot>], search: &str, filters: &[MetaFilter], ignore_diacritics: bool) -> Vec<Control> {
+    if search.is_empty() {
+        return scan.finish(Vec::<((), _)>::new());
+    }
+    let search = fold(search, ignore_diacritics).into_owned();
+    let contains = |root: &Root, path: &str, entry: &Entry| {
+        let relative = root.relative(path);
+        let found = fold(relative.strip_prefix("./").unwrap_or(relative), ignore_diacritics).contains(&search) && passes(relative, entry, filters);
+        found.then_some(relative.len())
+    };
+    let grams = trigrams(&search);
+    return scan.ranked(snapshots, Vec::new(), |_tree, snapshot, segment| {
+        let contains = |path: &str, entry: &Entry| contains(&snapshot.root, path, entry);
+        if grams.is_empty() {
+            // too short for a trigram, but short queries are cheap to check directly
+            scored(snapshot, segment.entries(), contains)
+        } else {
+            scored(snapshot, segment.with_trigrams(&grams).into_par_iter(), contains)
         }
+    });
+}
+
+// every path the regex or glob matches, shortest first like the infix results
+fn pattern_search(scan: Scan, snapshots: &[Arc<Snapshot>], pattern: &PathPattern, filters: &[MetaFilter]) -> Vec<Control> {
+    return scan.ranked(snapshots, Vec::new(), |_tree, snapshot, segment| {
+        scored(snapshot, segment.entries(), |path, entry| {
+            let relative = snapshot.root.relative(path);
+            (pattern.is_match(relative) && passes(relative, entry, filters)).then_some(relative.len())
+        })
+    });
+}
+
+// a query made of nothing but filters lists everything that passes them, in path order
+fn filter_search(scan: Scan, snapshots: &[Arc<Snapshot>], filters: &[MetaFilter]) -> Vec<Control> {
+    return scan.ranked(snapshots, Vec::new(), |_tree, snapshot, segment| {
+        scored(snapshot, segment.entries(), |path, entry| passes(snapshot.root.relative(path), entry, filters).then_some(()))
+    });
+}
 
-        i -= 16;
+// greps the contents of every indexed file, indexing carries on meanwhile into newer snapshots.
+// Hits stay in path and line order however the segments split the files up
+fn grep_search(scan: Scan, snapshots: &[Arc<Snapshot>], search: &str, filters: &[MetaFilter]) -> Vec<Control> {
+    if search.is_empty() {
+        return scan.finish(Vec::<((), _)>::new());
     }
+    return scan.ranked(snapshots, Vec::new(), |_tree, snapshot, segment| {
+        let paths: Vec<Arc<EntryPath>> = scored(snapshot, segment.entries(), |path, entry| passes(snapshot.root.relative(path), entry, filters).then_some(()))
+            .into_iter()
+            .map(|(_key, path)| path)
+            .collect();
+        grep_files(&paths, search).into_iter()
+            .filter_map(|hit| {
+                let key = parse_hit(&hit).map(|(path, line)| (path.to_path_buf(), line))?;
+                Some((key, hit))
+            })
+            .collect()
+    });
+}
+
+// [pad][byte][byte][byte]
+// [u8 ][u8  ][u8  ][u8  ] = [u32]
+pub fn trigrams(s: &str) -> Vec<u32> {
+    let s = fold(s.strip_prefix("./").unwrap_or(s), true);
+    let mut grams: Vec<u32> = s.as_bytes()
+        .windows(3)
+        .map(|w| (w[0] as u32) << 16 | (w[1] as u32) << 8 | w[2] as u32)
+        .collect();
+    grams.sort_unstable();
+    grams.dedup();
+    return grams;
+}
+
+// how often a walk in progress shows what it has found so far
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+
+// walks the tree, giving up as soon as a message comes in that makes the walk moot. Everything
+// that came in meanwhile ends up in `pending` either way
+fn index_directories(tree: &mut Tree, inbox: &mut Inbox, outlet: &Outlet, pending: &mut Vec<Control>) -> Option<Vec<CachedEntry>> {
+    let mut cached: Vec<CachedEntry> = Vec::new();
+    let mut published = Instant::now();
+    let pending_dirs = Arc::new(AtomicUsize::new(1));
+    for entry in tree.filter.counting(pending_dirs.clone()).walker(&tree.walk_root, 0) {
+        if published.elapsed() >= PUBLISH_INTERVAL {
+            let messages = inbox.check();
+            let moot = messages.iter().any(|message| match message {
+                Control::Halt | Control::Reindex | Control::FilterChanged | Control::RootChanged(_) => true,
+                Control::DirChanged(dir) => tree.outgrown_by(dir),
+                _ => false,
+            });
+            pending.extend(messages);
+            if moot {
+                return None;
+            }
+            outlet.publish(tree);
+            outlet.pending_dirs(pending_dirs.load(Ordering::Relaxed));
+            published = Instant::now();
+        }
+        let mut meta = None;
+        if let Some(cached_entry) = entry.as_ref().ok().and_then(|entry| CachedEntry::from_dir_entry(entry, 0)) {
+            meta = cached_entry.meta;
+            cached.push(cached_entry);
+        }
+        if let Some((path, _file_name, depth)) = get_file_and_path(entry) {
+            tree.builder.push(&path, depth, meta);
+        }
+    }
+    outlet.publish(tree);
+    return Some(cached);
+}
 
-    // Check the remaining part using linear search
-    for j in 0..s.len() {
-        if s.chars().nth(s.len()-j-1) == Some('/') {
-            return &s[s.len()-j..];
+fn index_cached(builder: &mut IndexBuilder, cached: &[CachedEntry]) {
+    for entry in cached {
+        if entry.depth == 0 {
+            continue;
         }
+        builder.push(&entry.path, entry.depth, entry.meta);
     }
+}
 
-    return s;
+//indexes 48gb in under 4s on ssd. lol
+
+pub fn last_chars_until_forward_slash(s: &str) -> &str {
+    // '/' never shows up inside a multi byte UTF-8 sequence, so the byte after one is always a
+    // char boundary and slicing there is safe for any path
+    match simd::rfind_byte(s.as_bytes(), b'/') {
+        Some(i) => return &s[i + 1..],
+        None => return s,
+    }
 }
 
 
 
 // [stack][char][hash]
 // [u16  ][u16 ][u32 ] = [u64]
-fn get_hashset(stack:u16, s: &str) -> Vec<u64>{
+pub(crate) fn get_hashset(stack:u16, s: &str) -> Vec<u64>{
     let mut hashset: Vec<u64> = Vec::new();
     let mut _num_c: u16;
     let mut _rolling_hash: u32 = 2;
@@ -420,36 +1097,7 @@ fn stack_hash(stack: u16, num_c: u16, rolling_hash: u32) -> u64 {
     return upper_u64 | lower_u64;
 }
 
+// byte wise, so both sides have to be folded the same way before they get here
 pub fn starts_with_prefix_simd(s: &str, prefix: &str) -> bool {
-    let s = s.to_lowercase();
-    let prefix = prefix.to_lowercase();
-
-    let input_bytes = s.as_bytes();
-    let prefix_bytes = prefix.as_bytes();
-    let prefix_len = prefix_bytes.len();
-
-    if prefix_len <= 16 {
-        input_bytes.starts_with(prefix_bytes)
-    } else {
-        // If the prefix size is greater than 16 bytes, use SIMD search
-        let mut i = 16;
-        while i < prefix_len-16 {
-            let input_chunk = u8x16::from_slice_unaligned(&input_bytes[i..(i+16)]);
-            let prefix_chunk = u8x16::from_slice_unaligned(&prefix_bytes[i..(i+16)]);
-            let cmp_result = input_chunk.eq(prefix_chunk);
-            if cmp_result.all() {
-                i += 16;
-            } else {
-                return false;
-            }
-        }
-
-        // Check the remaining part using linear search
-        if i < input_bytes.len() {
-            return input_bytes[i..].starts_with(&prefix_bytes[i..]);
-        }else{
-            return false;
-        }
-    }
+    return simd::starts_with(s.as_bytes(), prefix.as_bytes());
 }
-
@@ -0,0 +1,271 @@
+/*
+ * Metadata filters for the `f` finder. Every indexed entry carries a small EntryMeta record that
+ * is filled in by the walker threads (and kept in the on disk cache), so a query like
+ * `main ext:rs size:>10k mtime:<2d` can be answered straight from memory without a single stat.
+ * Filter tokens can sit anywhere in the query, everything else is the name query as before.
+ *
+ *   type:f|d|l        regular file, directory or symlink
+ *   ext:rs,toml       any of these extensions, case insensitive
+ *   size:>100M        > >= < <= =, bare means at least, units b k m g t (1024 based)
+ *   mtime:<2d         age compared to now, bare means at most, units s m h d w y
+ *   perm:x / perm:755 every letter set for someone (like find -perm /111), or an exact mode
+ *   owner:me          me, a user name or a uid
+ */
+
+use std::{
+    fs,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use serde::{Serialize, Deserialize};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EntryMeta {
+    pub size: u64,
+    // nanoseconds since the epoch, same unit as cache::mtime_of
+    pub mtime: u64,
+    // st_mode, file type bits included
+    pub mode: u32,
+    pub uid: u32,
+}
+
+impl EntryMeta {
+    pub fn from_metadata(metadata: &fs::Metadata) -> EntryMeta {
+        let mtime = metadata.modified().ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since_epoch| since_epoch.as_nanos() as u64);
+        return EntryMeta {
+            size: metadata.len(),
+            mtime,
+            mode: metadata.permissions().mode(),
+            uid: metadata.uid(),
+        };
+    }
+
+    // with `follow` a symlink describes its target, a dangling one still describes itself
+    pub fn of(path: &Path, follow: bool) -> Option<EntryMeta> {
+        let mut metadata = None;
+        if follow {
+            metadata = fs::metadata(path).ok();
+        }
+        if metadata.is_none() {
+            metadata = fs::symlink_metadata(path).ok();
+        }
+        return metadata.map(|metadata| EntryMeta::from_metadata(&metadata));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Equal,
+}
+
+impl Cmp {
+    fn test(&self, left: u64, right: u64) -> bool {
+        match self {
+            Cmp::Less => return left < right,
+            Cmp::LessEq => return left <= right,
+            Cmp::Greater => return left > right,
+            Cmp::GreaterEq => return left >= right,
+            Cmp::Equal => return left == right,
+        }
+    }
+
+    // age < 2d is the same as mtime > now - 2d
+    fn flipped(&self) -> Cmp {
+        match self {
+            Cmp::Less => return Cmp::Greater,
+            Cmp::LessEq => return Cmp::GreaterEq,
+            Cmp::Greater => return Cmp::Less,
+            Cmp::GreaterEq => return Cmp::LessEq,
+            Cmp::Equal => return Cmp::Equal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetaFilter {
+    Type(u32),
+    Ext(Vec<String>),
+    Size(Cmp, u64),
+    Mtime(Cmp, u64),
+    PermBits(u32),
+    PermExact(u32),
+    Owner(u32),
+}
+
+impl MetaFilter {
+    pub fn matches(&self, path: &str, meta: &EntryMeta) -> bool {
+        match self {
+            MetaFilter::Type(kind) => return meta.mode & S_IFMT == *kind,
+            MetaFilter::Ext(extensions) => {
+                let Some(ext) = Path::new(path).extension().and_then(|ext| ext.to_str()) else {
+                    return false;
+                };
+                let ext = ext.to_lowercase();
+                return extensions.contains(&ext);
+            },
+            MetaFilter::Size(cmp, size) => return cmp.test(meta.size, *size),
+            MetaFilter::Mtime(cmp, cutoff) => return cmp.test(meta.mtime, *cutoff),
+            // each rwx letter has to be set for at least one of user, group or other
+            MetaFilter::PermBits(bits) => {
+                return [0o4, 0o2, 0o1].iter()
+                    .filter(|letter| bits & **letter != 0)
+                    .all(|letter| meta.mode & (letter << 6 | letter << 3 | letter) != 0);
+            },
+            MetaFilter::PermExact(mode) => return meta.mode & 0o7777 == *mode,
+            MetaFilter::Owner(uid) => return meta.uid == *uid,
+        }
+    }
+}
+
+fn split_cmp(value: &str, bare: Cmp) -> (Cmp, &str) {
+    for (prefix, cmp) in [(">=", Cmp::GreaterEq), ("<=", Cmp::LessEq), (">", Cmp::Greater), ("<", Cmp::Less), ("=", Cmp::Equal)] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (cmp, rest);
+        }
+    }
+    return (bare, value);
+}
+
+fn split_number(value: &str) -> Option<(f64, String)> {
+    let digits = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let number = value[..digits].parse::<f64>().ok()?;
+    return Some((number, value[digits..].to_lowercase()));
+}
+
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = split_number(value)?;
+    let unit = unit.trim_end_matches("ib").trim_end_matches('b');
+    let scale: u64 = match unit {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return None,
+    };
+    return Some((number * scale as f64) as u64);
+}
+
+fn parse_age(value: &str) -> Option<u64> {
+    let (number, unit) = split_number(value)?;
+    let scale: u64 = match unit.as_str() {
+        "" | "s" => 1,
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        "y" => 60 * 60 * 24 * 365,
+        _ => return None,
+    };
+    return Some((number * scale as f64 * 1e9) as u64);
+}
+
+fn parse_owner(value: &str) -> Option<u32> {
+    if value == "me" {
+        return Some(nix::unistd::getuid().as_raw());
+    }
+    if let Ok(uid) = value.parse::<u32>() {
+        return Some(uid);
+    }
+    let user = nix::unistd::User::from_name(value).ok()??;
+    return Some(user.uid.as_raw());
+}
+
+fn parse_filter(key: &str, value: &str, now: u64) -> Result<MetaFilter, String> {
+    let invalid = || format!("{}: can't read '{}'", key, value);
+    match key {
+        "type" => {
+            let kind = match value {
+                "f" | "file" => S_IFREG,
+                "d" | "dir" | "directory" => S_IFDIR,
+                "l" | "link" | "symlink" => S_IFLNK,
+                _ => return Err(invalid()),
+            };
+            return Ok(MetaFilter::Type(kind));
+        },
+        "ext" => {
+            let extensions: Vec<String> = value.split(',')
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect();
+            if extensions.is_empty() {
+                return Err(invalid());
+            }
+            return Ok(MetaFilter::Ext(extensions));
+        },
+        "size" => {
+            let (cmp, value) = split_cmp(value, Cmp::GreaterEq);
+            return parse_size(value).map(|size| MetaFilter::Size(cmp, size)).ok_or_else(invalid);
+        },
+        "mtime" => {
+            let (cmp, value) = split_cmp(value, Cmp::LessEq);
+            let age = parse_age(value).ok_or_else(invalid)?;
+            return Ok(MetaFilter::Mtime(cmp.flipped(), now.saturating_sub(age)));
+        },
+        "perm" => {
+            if !value.is_empty() && value.chars().all(|c| ('0'..='7').contains(&c)) {
+                return u32::from_str_radix(value, 8).map(MetaFilter::PermExact).map_err(|_| invalid());
+            }
+            let mut bits = 0;
+            for c in value.chars() {
+                bits |= match c {
+                    'r' => 0o4,
+                    'w' => 0o2,
+                    'x' => 0o1,
+                    _ => return Err(invalid()),
+                };
+            }
+            if bits == 0 {
+                return Err(invalid());
+            }
+            return Ok(MetaFilter::PermBits(bits));
+        },
+        "owner" => return parse_owner(value).map(MetaFilter::Owner).ok_or_else(|| format!("owner: no user '{}'", value)),
+        _ => return Err(invalid()),
+    }
+}
+
+const KEYS: [&str; 6] = ["type", "ext", "size", "mtime", "perm", "owner"];
+
+/// Pulls the filter tokens out of a query. Returns the filters and whatever is left for the name
+/// matchers; a query without any filter tokens comes back untouched.
+pub fn split_filters(query: &str) -> Result<(Vec<MetaFilter>, String), String> {
+    let is_filter = |token: &str| {
+        token.split_once(':').is_some_and(|(key, _value)| KEYS.contains(&key))
+    };
+    if !query.split_whitespace().any(is_filter) {
+        return Ok((Vec::new(), query.to_string()));
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_nanos() as u64);
+    let mut filters = Vec::new();
+    let mut rest: Vec<&str> = Vec::new();
+    for token in query.split_whitespace() {
+        match token.split_once(':') {
+            Some((key, value)) if KEYS.contains(&key) => filters.push(parse_filter(key, value, now)?),
+            _ => rest.push(token),
+        }
+    }
+    return Ok((filters, rest.join(" ")));
+}
+
+pub fn matches_all(filters: &[MetaFilter], path: &str, meta: Option<&EntryMeta>) -> bool {
+    if filters.is_empty() {
+        return true;
+    }
+    let Some(meta) = meta else {
+        return false;
+    };
+    return filters.iter().all(|filter| filter.matches(path, meta));
+}
@@ -13,7 +13,8 @@ use std::{
     process::Command,
     env,
     fs,
-    collections::HashSet,
+    path::Path,
+    collections::{BTreeMap, HashSet},
 };
 const DEFAULT_CONFIG: &str = r#"
 [[app_rule]]
@@ -23,6 +24,21 @@ const DEFAULT_CONFIG: &str = r#"
 
 [special_rule]
     app = "vim"
+
+[indexer]
+    respect_ignore = true
+    exclude = []
+    include = []
+    one_file_system = false
+    follow_symlinks = false
+    project_markers = [".git", "Cargo.toml", "package.json"]
+
+[search]
+    ignore_diacritics = true
+    max_typos = 1
+    sort = ["score", "depth", "name"]
+
+[roots]
 "#;
 
 
@@ -30,6 +46,13 @@ const DEFAULT_CONFIG: &str = r#"
 pub struct Config {
     app_rule: Vec<AppRule>,
     special_rule: SpecialRule,
+    #[serde(default)]
+    pub indexer: IndexerConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    // name = "path" for `:root name`, see roots.rs
+    #[serde(default)]
+    pub roots: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -44,6 +67,83 @@ pub struct SpecialRule{
     app: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndexerConfig{
+    // skip whatever .gitignore, .ignore and .jefignore files rule out
+    #[serde(default = "default_true")]
+    pub respect_ignore: bool,
+    // globs matched against the path from the index root, a bare name matches at any depth
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // when set, only files matching one of these are indexed (directories are always walked)
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    // stay on the root's filesystem, skipping /proc, network and fuse mounts
+    #[serde(default)]
+    pub one_file_system: bool,
+    // symlinked directories are walked, loops back into an ancestor are not
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    // the fuzzy finder covers the nearest directory up from the current one holding any of these
+    #[serde(default = "default_project_markers")]
+    pub project_markers: Vec<String>,
+}
+
+impl Default for IndexerConfig {
+    fn default() -> IndexerConfig {
+        return toml::from_str("").unwrap();
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchConfig{
+    // "resume" finds "résumé", turn off to make accents count
+    #[serde(default = "default_true")]
+    pub ignore_diacritics: bool,
+    // how many typos the fuzzy finder forgives in a name, one per four characters typed, 0 for none
+    #[serde(default = "default_max_typos")]
+    pub max_typos: usize,
+    // how fuzzy results are ordered, by the first key and then the next, the path breaks any tie left
+    #[serde(default = "default_sort")]
+    pub sort: Vec<SortKey>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    // best match first
+    Score,
+    // shallowest first
+    Depth,
+    Name,
+    // newest first
+    Mtime,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        return toml::from_str("").unwrap();
+    }
+}
+
+fn default_true() -> bool {
+    return true;
+}
+
+fn default_max_typos() -> usize {
+    return 1;
+}
+
+fn default_sort() -> Vec<SortKey> {
+    return vec![SortKey::Score, SortKey::Depth, SortKey::Name];
+}
+
+fn default_project_markers() -> Vec<String> {
+    return [".git", "Cargo.toml", "package.json"].iter().map(|marker| marker.to_string()).collect();
+}
+
 impl Config{
     pub fn app_from_type(&mut self, extension: String) -> Option<AppRule>{
         for app_rule in &self.app_rule{
@@ -132,14 +232,13 @@ pub fn special_open<B: Backend>(terminal: &mut Terminal<B>){
     }
 }
 
-pub fn open<B: Backend>(terminal: &mut Terminal<B>, path: String){
-    let extension = std::path::Path::new(&path);
-    let extension = extension.extension();
-    if let Some(extension) = extension.and_then(std::ffi::OsStr::to_str) {
+// takes the path as it is on disk, so names that are not valid UTF-8 open just the same
+pub fn open<B: Backend>(terminal: &mut Terminal<B>, path: &Path){
+    if let Some(extension) = path.extension().and_then(std::ffi::OsStr::to_str) {
         let mut config = Config::default_config();
         if let Some(app_rule) = config.app_from_type(extension.to_string()){
             if app_rule.tui {
-                open_tui_app(terminal, app_rule.app, &path);
+                open_tui_app(terminal, app_rule.app, path);
             } else {
                 let _ = open::with_detached(path, app_rule.app);            
             }
@@ -149,14 +248,37 @@ pub fn open<B: Backend>(terminal: &mut Terminal<B>, path: String){
     }
 }
 
-pub fn open_tui_app<B: Backend>(terminal: &mut Terminal<B>,command: String, path: &String){
+// used by grep results, editors all take `+N` to jump to a line
+pub fn open_at_line<B: Backend>(terminal: &mut Terminal<B>, path: &Path, line: usize){
+    let extension = path.extension().and_then(std::ffi::OsStr::to_str).unwrap_or_default();
+    let mut config = Config::default_config();
+    let app = match config.app_from_type(extension.to_string()) {
+        Some(app_rule) if app_rule.tui => Some(app_rule.app),
+        Some(_) => None,
+        None => env::var("EDITOR").ok().filter(|editor| !editor.is_empty()),
+    };
+    match app {
+        Some(app) => open_tui_app_at(terminal, app, path, Some(line)),
+        None => open(terminal, path),
+    }
+}
+
+pub fn open_tui_app<B: Backend>(terminal: &mut Terminal<B>,command: String, path: &Path){
+    open_tui_app_at(terminal, command, path, None);
+}
+
+fn open_tui_app_at<B: Backend>(terminal: &mut Terminal<B>,command: String, path: &Path, line: Option<usize>){
     let _ = disable_raw_mode();
     let _ = execute!(
         std::io::stdout(),
         LeaveAlternateScreen,
         DisableMouseCapture
     );
-    let _ = std::process::Command::new(command)
+    let mut command = std::process::Command::new(command);
+    if let Some(line) = line {
+        command.arg(format!("+{}", line));
+    }
+    let _ = command
         .arg(path)
         .status()
         .expect("Failed to open shell");
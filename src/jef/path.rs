@@ -0,0 +1,136 @@
+/*
+ * Paths as the index, the finders and the list widgets see them. A Linux file name is a string
+ * of bytes, not text, and one that isn't valid UTF-8 has no String form at all, so it used to be
+ * dropped by the walker and never show up anywhere. An EntryPath keeps two spellings: the text
+ * that hashing, matching and drawing work on, where every byte that isn't part of valid UTF-8 is
+ * written as \xNN, and, only when that text isn't the real name, the exact bytes from the disk.
+ * Anything that touches the filesystem goes through as_path(), so stat'ing, watching and opening
+ * always use the name as it is on disk.
+ */
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    fmt,
+    ops::Deref,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+};
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntryPath {
+    text: String,
+    // None whenever text already is the path
+    raw: Option<OsString>,
+}
+
+/// Lossy but readable, every byte that is not part of valid UTF-8 becomes `\xNN`.
+pub fn escape(name: &OsStr) -> Cow<'_, str> {
+    let mut bytes = name.as_bytes();
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(bytes.len() + 8);
+    while !bytes.is_empty() {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => {
+                escaped.push_str(text);
+                break;
+            },
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                escaped.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                let invalid = err.error_len().unwrap_or(rest.len());
+                for b in &rest[..invalid] {
+                    escaped.push_str(&format!("\\x{:02x}", b));
+                }
+                bytes = &rest[invalid..];
+            },
+        }
+    }
+    return Cow::Owned(escaped);
+}
+
+impl EntryPath {
+    pub fn new(path: &Path) -> EntryPath {
+        match path.to_str() {
+            Some(text) => return EntryPath::from(text),
+            None => return EntryPath {
+                text: escape(path.as_os_str()).into_owned(),
+                raw: Some(path.as_os_str().to_os_string()),
+            },
+        }
+    }
+
+    /// Shows `text` in the lists but still leads to `path`, used for grep results.
+    pub fn labelled(text: String, path: &Path) -> EntryPath {
+        return EntryPath {
+            text,
+            raw: Some(path.as_os_str().to_os_string()),
+        };
+    }
+
+    pub fn is_label(&self) -> bool {
+        return self.raw.as_ref().is_some_and(|raw| escape(raw) != self.text);
+    }
+
+    pub fn as_path(&self) -> &Path {
+        match &self.raw {
+            Some(raw) => return Path::new(raw),
+            None => return Path::new(&self.text),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        return self.as_path().as_os_str().as_bytes();
+    }
+
+    pub fn join(&self, name: &OsStr) -> EntryPath {
+        if self.raw.is_none() {
+            if let Some(name) = name.to_str() {
+                return EntryPath::from(format!("{}/{}", self.text, name));
+            }
+        }
+        return EntryPath::new(&self.as_path().join(name));
+    }
+
+    pub fn file_name(&self) -> &str {
+        match self.text.rfind('/') {
+            Some(i) => return &self.text[i + 1..],
+            None => return &self.text,
+        }
+    }
+}
+
+impl From<String> for EntryPath {
+    fn from(text: String) -> EntryPath {
+        return EntryPath { text, raw: None };
+    }
+}
+
+impl From<&str> for EntryPath {
+    fn from(text: &str) -> EntryPath {
+        return EntryPath::from(text.to_string());
+    }
+}
+
+impl Deref for EntryPath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        return &self.text;
+    }
+}
+
+impl fmt::Display for EntryPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(&self.text);
+    }
+}
+
+impl fmt::Debug for EntryPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Debug::fmt(self.as_path(), f);
+    }
+}
@@ -0,0 +1,115 @@
+/*
+ * Explicit pattern syntaxes for both finders. `re:` turns the rest of the query into a regular
+ * expression that is tried against the file name and against the path relative to where the
+ * search started. `glob:`, or any query with a `*` or `?` in it, is a glob over that relative
+ * path, and a glob without a slash matches the name at any depth, the same as the exclude globs
+ * in jef.toml. The REGEX and GLOB modes of `f` read the whole query that way without a prefix.
+ * Both use smart case: a pattern with no capitals in it matches any case. A pattern that does not
+ * compile comes back as an error message for the status bar, never as an empty result list.
+ */
+
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use crate::jef::flags::SearchMode;
+
+// set by the search threads when the current query does not compile, shown in the status bar
+pub type QueryError = Arc<Mutex<Option<String>>>;
+
+#[derive(Debug, Clone)]
+pub enum PathPattern {
+    Regex(Regex),
+    Glob(GlobMatcher),
+}
+
+impl PathPattern {
+    pub fn is_match(&self, path: &str) -> bool {
+        let relative = path.strip_prefix("./").unwrap_or(path);
+        match self {
+            PathPattern::Regex(regex) => {
+                let name = relative.rsplit('/').next().unwrap_or(relative);
+                return regex.is_match(name) || regex.is_match(relative);
+            },
+            PathPattern::Glob(glob) => return glob.is_match(relative),
+        }
+    }
+
+    /// What a regex matched in `path` as byte ranges, its groups when it has any that took part.
+    /// A glob matches the path as a whole and marks nothing.
+    pub fn ranges(&self, path: &str) -> Vec<Range<usize>> {
+        let offset = path.len() - path.strip_prefix("./").unwrap_or(path).len();
+        let name = path.rfind('/').map_or(0, |i| i + 1);
+        let PathPattern::Regex(regex) = self else {
+            return Vec::new();
+        };
+        // the name first, the same as is_match
+        let Some((start, captures)) = [name, offset].into_iter()
+            .find_map(|start| regex.captures(&path[start..]).map(|captures| (start, captures))) else {
+            return Vec::new();
+        };
+        let groups: Vec<Range<usize>> = captures.iter().skip(1).flatten().map(|group| group.range()).collect();
+        let matched = match groups.is_empty() {
+            true => captures.get(0).map(|whole| whole.range()).into_iter().collect(),
+            false => groups,
+        };
+        return matched.into_iter().map(|range| range.start + start..range.end + start).collect();
+    }
+}
+
+fn smart_case(pattern: &str) -> bool {
+    return !pattern.chars().any(|c| c.is_uppercase());
+}
+
+fn compile_regex(pattern: &str) -> Result<PathPattern, String> {
+    return RegexBuilder::new(pattern)
+        .case_insensitive(smart_case(pattern))
+        .build()
+        .map(PathPattern::Regex)
+        // syntax errors come with an ascii art pointer over several lines, the last one says what is wrong
+        .map_err(|err| {
+            let message = err.to_string();
+            let reason = message.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default();
+            format!("regex: {}", reason.trim().trim_start_matches("error: "))
+        });
+}
+
+fn compile_glob(pattern: &str) -> Result<PathPattern, String> {
+    let pattern = pattern.trim_start_matches("./");
+    let pattern = if pattern.contains('/') || pattern.starts_with("**") {
+        pattern.to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    return GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .case_insensitive(smart_case(&pattern))
+        .build()
+        .map(|glob| PathPattern::Glob(glob.compile_matcher()))
+        .map_err(|err| format!("glob: {}", err.kind()));
+}
+
+/// Works out whether the query is a regex or a glob. `Ok(None)` means it is neither and the
+/// finder should fall back to its normal matching.
+pub fn parse_pattern(query: &str, mode: SearchMode) -> Result<Option<PathPattern>, String> {
+    if query.is_empty() || mode == SearchMode::Grep {
+        return Ok(None);
+    }
+    if let Some(pattern) = query.strip_prefix("re:") {
+        return compile_regex(pattern).map(Some);
+    }
+    if let Some(pattern) = query.strip_prefix("glob:") {
+        return compile_glob(pattern).map(Some);
+    }
+    match mode {
+        SearchMode::Regex => return compile_regex(query).map(Some),
+        SearchMode::Glob => return compile_glob(query).map(Some),
+        _ => {},
+    }
+    if query.contains('*') || query.contains('?') {
+        return compile_glob(query).map(Some);
+    }
+    return Ok(None);
+}
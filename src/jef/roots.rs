@@ -0,0 +1,73 @@
+/*
+ * Which trees the fuzzy finder covers. By default that is the project JEF is in: the nearest
+ * directory up from the current one holding a marker like .git or Cargo.toml, so stepping into
+ * src/ keeps the whole project searchable instead of re-indexing just the subdirectory. `P` flips
+ * between that and the current directory alone. The [roots] section of jef.toml names other trees
+ * (`notes = "~/notes"`), and `:root notes` points the finder at one of them or `:root all` at all
+ * of them together. `:root` on its own goes back to the project and `:root .` to the current
+ * directory. Named roots are indexed the first time they are picked and then kept, watched and
+ * all, so switching back and forth never walks them again. Their paths are absolute, since they
+ * have nothing to do with the current directory, but only the part below the root is matched and
+ * the list shows the root's name in its place.
+ */
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootScope {
+    // the project around the current directory, or the directory itself outside of one
+    Project,
+    CurrentDir,
+    Named(String),
+    All,
+}
+
+impl RootScope {
+    /// Reads the argument of `:root`, naming a root that is not configured is an error.
+    pub fn parse(arg: &str, roots: &BTreeMap<String, String>) -> Result<RootScope, String> {
+        match arg.trim() {
+            "" => return Ok(RootScope::Project),
+            "." => return Ok(RootScope::CurrentDir),
+            "all" => return Ok(RootScope::All),
+            name if roots.contains_key(name) => return Ok(RootScope::Named(name.to_string())),
+            name => return Err(format!("no root named {} in jef.toml", name)),
+        }
+    }
+
+    /// The configured roots this scope covers, by name.
+    pub fn names<'a>(&'a self, roots: &'a BTreeMap<String, String>) -> Vec<&'a str> {
+        match self {
+            RootScope::Project | RootScope::CurrentDir => return Vec::new(),
+            RootScope::Named(name) => return vec![name.as_str()],
+            RootScope::All => return roots.keys().map(|name| name.as_str()).collect(),
+        }
+    }
+
+    /// What `P` switches to, the current directory from the project and the project from anything else.
+    pub fn toggle_project(&self) -> RootScope {
+        match self {
+            RootScope::Project => return RootScope::CurrentDir,
+            _ => return RootScope::Project,
+        }
+    }
+}
+
+/// A configured root as an absolute path, with a leading `~` meaning the home directory.
+pub fn root_path(configured: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let path = match (configured.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => home.join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(configured),
+    };
+    return std::env::current_dir().unwrap_or_default().join(path);
+}
+
+/// The nearest of `dir` and its ancestors that holds one of the markers, if any does.
+pub fn project_root(dir: &Path, markers: &[String]) -> Option<PathBuf> {
+    return dir.ancestors()
+        .find(|ancestor| markers.iter().any(|marker| ancestor.join(marker).symlink_metadata().is_ok()))
+        .map(Path::to_path_buf);
+}
@@ -0,0 +1,243 @@
+/*
+ * The two byte loops the index leans on hardest, finding the last '/' of a path and comparing a
+ * file name against a prefix, written with std::arch so they build on stable. The widest level
+ * the CPU has (AVX2 or SSE2 on x86_64, NEON on aarch64) is picked once at runtime, everything
+ * else gets the plain scalar loop. Before a level is trusted it has to agree with the scalar loop
+ * on self_check's inputs, so a bad vector path costs speed instead of wrong results.
+ */
+
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Scalar,
+    Sse2,
+    Avx2,
+    Neon,
+}
+
+impl Level {
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Level::Scalar => return true,
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse2 => return is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "x86_64")]
+            Level::Avx2 => return is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "aarch64")]
+            Level::Neon => return std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => return false,
+        }
+    }
+
+    // every level this CPU can run, widest first
+    pub fn available() -> Vec<Level> {
+        return [Level::Avx2, Level::Sse2, Level::Neon, Level::Scalar].into_iter()
+            .filter(|level| level.is_supported())
+            .collect();
+    }
+}
+
+/// The level in use, picked on first call: the widest one that passes self_check.
+pub fn level() -> Level {
+    static LEVEL: OnceLock<Level> = OnceLock::new();
+    return *LEVEL.get_or_init(|| {
+        return Level::available().into_iter()
+            .find(|level| self_check(*level).is_ok())
+            .unwrap_or(Level::Scalar);
+    });
+}
+
+pub fn rfind_byte(bytes: &[u8], needle: u8) -> Option<usize> {
+    return rfind_byte_using(level(), bytes, needle);
+}
+
+pub fn starts_with(bytes: &[u8], prefix: &[u8]) -> bool {
+    return starts_with_using(level(), bytes, prefix);
+}
+
+// a level the CPU does not have quietly runs as scalar, so these are safe to call with any level
+pub fn rfind_byte_using(level: Level, bytes: &[u8], needle: u8) -> Option<usize> {
+    if !level.is_supported() {
+        return rfind_byte_scalar(bytes, needle);
+    }
+    match level {
+        // safety: the target features were detected just above
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => return unsafe { x86::rfind_byte_avx2(bytes, needle) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Sse2 => return unsafe { x86::rfind_byte_sse2(bytes, needle) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => return unsafe { arm::rfind_byte_neon(bytes, needle) },
+        _ => return rfind_byte_scalar(bytes, needle),
+    }
+}
+
+pub fn starts_with_using(level: Level, bytes: &[u8], prefix: &[u8]) -> bool {
+    if prefix.len() > bytes.len() {
+        return false;
+    }
+    if !level.is_supported() {
+        return starts_with_scalar(bytes, prefix);
+    }
+    match level {
+        // safety: the target features were detected just above
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => return unsafe { x86::starts_with_avx2(bytes, prefix) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Sse2 => return unsafe { x86::starts_with_sse2(bytes, prefix) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => return unsafe { arm::starts_with_neon(bytes, prefix) },
+        _ => return starts_with_scalar(bytes, prefix),
+    }
+}
+
+fn rfind_byte_scalar(bytes: &[u8], needle: u8) -> Option<usize> {
+    return bytes.iter().rposition(|b| *b == needle);
+}
+
+fn starts_with_scalar(bytes: &[u8], prefix: &[u8]) -> bool {
+    return bytes.starts_with(prefix);
+}
+
+/// Runs `level` and the scalar loops over inputs of every length around the vector widths, with
+/// the needle at every position, and reports the first input they disagree on.
+pub fn self_check(level: Level) -> Result<(), String> {
+    if !level.is_supported() {
+        return Err(format!("{:?} is not supported on this CPU", level));
+    }
+    for len in 0..=80 {
+        let mut bytes: Vec<u8> = (0..len).map(|i| b'a' + (i % 26) as u8).collect();
+        for slash in 0..=len {
+            if slash < len {
+                bytes[slash] = b'/';
+            }
+            let expected = rfind_byte_scalar(&bytes, b'/');
+            let got = rfind_byte_using(level, &bytes, b'/');
+            if got != expected {
+                return Err(format!("{:?}: last '/' of {} bytes, slash at {}: got {:?}, expected {:?}", level, len, slash, got, expected));
+            }
+            for prefix_len in [0, 1, 15, 16, 17, 31, 32, 33, len.saturating_sub(1), len, len + 1] {
+                let mut prefix: Vec<u8> = bytes.iter().copied().take(prefix_len).collect();
+                prefix.resize(prefix_len, b'z');
+                for flip in [None, Some(0), Some(prefix_len.saturating_sub(1)), Some(prefix_len / 2)] {
+                    let mut prefix = prefix.clone();
+                    if let Some(at) = flip.filter(|at| *at < prefix_len) {
+                        prefix[at] ^= 0x80;
+                    }
+                    let expected = starts_with_scalar(&bytes, &prefix);
+                    let got = starts_with_using(level, &bytes, &prefix);
+                    if got != expected {
+                        return Err(format!("{:?}: prefix of {} bytes against {} bytes: got {}, expected {}", level, prefix_len, len, got, expected));
+                    }
+                }
+            }
+            if slash < len {
+                bytes[slash] = b'a' + (slash % 26) as u8;
+            }
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use super::{rfind_byte_scalar, starts_with_scalar};
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn rfind_byte_sse2(bytes: &[u8], needle: u8) -> Option<usize> {
+        let splat = _mm_set1_epi8(needle as i8);
+        let mut end = bytes.len();
+        while end >= 16 {
+            let chunk = _mm_loadu_si128(bytes.as_ptr().add(end - 16) as *const __m128i);
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(chunk, splat)) as u32;
+            if mask != 0 {
+                return Some(end - 16 + (31 - mask.leading_zeros()) as usize);
+            }
+            end -= 16;
+        }
+        return rfind_byte_scalar(&bytes[..end], needle);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn rfind_byte_avx2(bytes: &[u8], needle: u8) -> Option<usize> {
+        let splat = _mm256_set1_epi8(needle as i8);
+        let mut end = bytes.len();
+        while end >= 32 {
+            let chunk = _mm256_loadu_si256(bytes.as_ptr().add(end - 32) as *const __m256i);
+            let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(chunk, splat)) as u32;
+            if mask != 0 {
+                return Some(end - 32 + (31 - mask.leading_zeros()) as usize);
+            }
+            end -= 32;
+        }
+        return rfind_byte_sse2(&bytes[..end], needle);
+    }
+
+    // callers make sure prefix is no longer than bytes
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn starts_with_sse2(bytes: &[u8], prefix: &[u8]) -> bool {
+        let mut i = 0;
+        while i + 16 <= prefix.len() {
+            let a = _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+            let b = _mm_loadu_si128(prefix.as_ptr().add(i) as *const __m128i);
+            if _mm_movemask_epi8(_mm_cmpeq_epi8(a, b)) != 0xFFFF {
+                return false;
+            }
+            i += 16;
+        }
+        return starts_with_scalar(&bytes[i..], &prefix[i..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn starts_with_avx2(bytes: &[u8], prefix: &[u8]) -> bool {
+        let mut i = 0;
+        while i + 32 <= prefix.len() {
+            let a = _mm256_loadu_si256(bytes.as_ptr().add(i) as *const __m256i);
+            let b = _mm256_loadu_si256(prefix.as_ptr().add(i) as *const __m256i);
+            if _mm256_movemask_epi8(_mm256_cmpeq_epi8(a, b)) != -1 {
+                return false;
+            }
+            i += 32;
+        }
+        return starts_with_sse2(&bytes[i..], &prefix[i..]);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use std::arch::aarch64::*;
+    use super::{rfind_byte_scalar, starts_with_scalar};
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn rfind_byte_neon(bytes: &[u8], needle: u8) -> Option<usize> {
+        let splat = vdupq_n_u8(needle);
+        let mut end = bytes.len();
+        while end >= 16 {
+            let chunk = vld1q_u8(bytes.as_ptr().add(end - 16));
+            // no movemask on NEON, so only ask whether the chunk has one and find it by hand
+            if vmaxvq_u8(vceqq_u8(chunk, splat)) != 0 {
+                return rfind_byte_scalar(&bytes[end - 16..end], needle).map(|i| end - 16 + i);
+            }
+            end -= 16;
+        }
+        return rfind_byte_scalar(&bytes[..end], needle);
+    }
+
+    // callers make sure prefix is no longer than bytes
+    #[target_feature(enable = "neon")]
+    pub unsafe fn starts_with_neon(bytes: &[u8], prefix: &[u8]) -> bool {
+        let mut i = 0;
+        while i + 16 <= prefix.len() {
+            let a = vld1q_u8(bytes.as_ptr().add(i));
+            let b = vld1q_u8(prefix.as_ptr().add(i));
+            if vminvq_u8(vceqq_u8(a, b)) != 0xFF {
+                return false;
+            }
+            i += 16;
+        }
+        return starts_with_scalar(&bytes[i..], &prefix[i..]);
+    }
+}
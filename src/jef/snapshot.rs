@@ -0,0 +1,446 @@
+/*
+ * The index as the search threads see it: an immutable Snapshot that gets replaced whole. The
+ * index thread is the only writer. It keeps an IndexBuilder, and whenever it has something new
+ * it builds just the parts that changed and publishes a fresh Snapshot through an ArcSwap. A
+ * search loads whatever snapshot is current without taking a lock and keeps it alive for as long
+ * as it needs it, so searching never waits on indexing and indexing never waits on a search.
+ *
+ * Entries live in segments of up to SEGMENT_SIZE. Each segment has its own name hash buckets and
+ * trigram postings, so segments are built on every core at once and searched the same way. A full
+ * segment never changes again except for removals (the entry becomes a tombstone, the postings
+ * stay) and metadata updates, and both copy the entry list while sharing the postings. New
+ * entries collect in a pending list that is rebuilt as the tail segment on each publish until it
+ * fills up and gets sealed.
+ *
+ * Every snapshot knows the Root its tree was walked from. Paths are kept whole so they open from
+ * anywhere, but searches match only what comes after the root, so the directories above it never
+ * match a query or throw off an anchor.
+ */
+
+use std::{
+    collections::HashMap,
+    mem::size_of,
+    path::Path,
+    sync::Arc,
+};
+use arc_swap::ArcSwap;
+use rayon::{
+    iter::{
+        IntoParallelRefIterator,
+        IntoParallelRefMutIterator,
+        ParallelIterator
+    },
+    slice::ParallelSlice,
+};
+use crate::jef::{
+    indexer::{get_hashset, trigrams},
+    meta::EntryMeta,
+    path::{escape, EntryPath},
+    tree::{PathTable, PathTree},
+};
+
+pub const SEGMENT_SIZE: usize = 1 << 14;
+
+pub type SharedIndex = Arc<ArcSwap<Snapshot>>;
+
+/// Every tree the finder covers right now, one index each. Switching roots just swaps the list.
+pub type SharedScope = Arc<ArcSwap<Vec<SharedIndex>>>;
+
+/// Where the paths of one tree start, "./" for the current directory. A named root goes by its
+/// name in the list instead of the directories above it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Root {
+    prefix: String,
+    label: Option<String>,
+}
+
+impl Root {
+    pub fn new(walk_root: &Path) -> Root {
+        let mut prefix = escape(walk_root.as_os_str()).into_owned();
+        if !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        return Root { prefix, label: None };
+    }
+
+    pub fn labelled(self, label: &str) -> Root {
+        return Root { label: Some(label.to_string()), ..self };
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        return self.label.as_deref();
+    }
+
+    /// Where the part of `path` below the root starts, 0 for a path from somewhere else.
+    pub fn offset(&self, path: &str) -> usize {
+        match path.starts_with(&self.prefix) {
+            true => return self.prefix.len(),
+            false => return 0,
+        }
+    }
+
+    pub fn relative<'a>(&self, path: &'a str) -> &'a str {
+        return &path[self.offset(path)..];
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    // the path's id in the PathTree, see tree.rs
+    pub node: u32,
+    pub depth: u16,
+    pub meta: Option<EntryMeta>,
+}
+
+#[derive(Debug, Default)]
+pub struct Segment {
+    // removed entries leave a None behind so the postings keep pointing at the right ids
+    entries: Vec<Option<Entry>>,
+    live: usize,
+    buckets: Arc<HashMap<u64, Vec<u32>>>,
+    trigrams: Arc<HashMap<u32, Vec<u32>>>,
+    stack: u16,
+}
+
+impl Segment {
+    fn build(entries: &[Entry], tree: &PathTable) -> Segment {
+        let mut buckets: HashMap<u64, Vec<u32>> = HashMap::new();
+        let mut grams: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut stack = 0;
+        let mut text = String::new();
+        for (id, entry) in entries.iter().enumerate() {
+            let id = id as u32;
+            stack = stack.max(entry.depth);
+            for hash in get_hashset(entry.depth, &tree.name(entry.node)) {
+                let bucket = buckets.entry(hash).or_default();
+                // two prefixes of one name can land in the same bucket
+                if bucket.last() != Some(&id) {
+                    bucket.push(id);
+                }
+            }
+            text.clear();
+            tree.write_text(entry.node, &mut text);
+            for gram in trigrams(&text) {
+                grams.entry(gram).or_default().push(id);
+            }
+        }
+        return Segment {
+            entries: entries.iter().copied().map(Some).collect(),
+            live: entries.len(),
+            buckets: Arc::new(buckets),
+            trigrams: Arc::new(grams),
+            stack,
+        };
+    }
+
+    pub fn live(&self) -> impl Iterator<Item = &Entry> {
+        return self.entries.iter().flatten();
+    }
+
+    pub fn entries(&self) -> impl ParallelIterator<Item = &Entry> {
+        return self.entries.par_iter().flatten();
+    }
+
+    pub fn bucket(&self, hash: u64) -> impl Iterator<Item = &Entry> {
+        let ids = self.buckets.get(&hash).map(|ids| ids.as_slice()).unwrap_or_default();
+        return ids.iter().filter_map(|id| self.entries[*id as usize].as_ref());
+    }
+
+    /// Every entry whose path holds all of `grams`, by intersecting the postings.
+    pub fn with_trigrams(&self, grams: &[u32]) -> Vec<&Entry> {
+        let mut postings: Vec<&Vec<u32>> = Vec::new();
+        for gram in grams {
+            match self.trigrams.get(gram) {
+                Some(posting) => postings.push(posting),
+                None => return Vec::new(),
+            }
+        }
+        postings.sort_by_key(|posting| posting.len());
+        let ids = match postings.split_first() {
+            Some((first, rest)) => rest.iter().fold((*first).clone(), |acc, posting| intersect_sorted(&acc, posting)),
+            None => Vec::new(),
+        };
+        return ids.iter().filter_map(|id| self.entries[*id as usize].as_ref()).collect();
+    }
+
+    // the hash of the whole file name is the last one in its hashset, so that bucket is the only
+    // place an exact path can be
+    fn position(&self, node: u32, name: &str, stack: u16) -> Option<usize> {
+        let hash = get_hashset(stack, name).pop()?;
+        let bucket = self.buckets.get(&hash)?;
+        return bucket.iter()
+            .map(|id| *id as usize)
+            .find(|id| self.entries[*id].is_some_and(|entry| entry.node == node));
+    }
+
+    // a copy with different entries but the same postings, which stay valid since ids never move
+    fn edited(&self, edit: impl FnOnce(&mut Vec<Option<Entry>>)) -> Segment {
+        let mut entries = self.entries.clone();
+        edit(&mut entries);
+        return Segment {
+            live: entries.iter().flatten().count(),
+            entries,
+            buckets: self.buckets.clone(),
+            trigrams: self.trigrams.clone(),
+            stack: self.stack,
+        };
+    }
+
+    // mostly tombstones by now, worth building fresh postings
+    fn is_sparse(&self) -> bool {
+        return self.live < self.entries.len() / 2;
+    }
+
+    fn compacted(&self, tree: &PathTable) -> Segment {
+        let entries: Vec<Entry> = self.live().copied().collect();
+        return Segment::build(&entries, tree);
+    }
+
+    fn bytes(&self) -> (usize, usize, usize) {
+        let entries = self.entries.capacity() * size_of::<Option<Entry>>();
+        let postings = |slot: usize, ids: &Vec<u32>| slot + size_of::<Vec<u32>>() + ids.capacity() * size_of::<u32>();
+        let buckets = self.buckets.values().map(|ids| postings(size_of::<u64>(), ids)).sum();
+        let trigrams = self.trigrams.values().map(|ids| postings(size_of::<u32>(), ids)).sum();
+        return (entries, buckets, trigrams);
+    }
+}
+
+/// Rough memory use of one snapshot, for `:stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IndexStats {
+    pub entries: usize,
+    pub nodes: usize,
+    pub segments: usize,
+    pub path_bytes: usize,
+    pub entry_bytes: usize,
+    pub bucket_bytes: usize,
+    pub trigram_bytes: usize,
+    pub lookup_bytes: usize,
+}
+
+impl IndexStats {
+    /// Adds another tree's numbers to these, for a scope of several roots.
+    pub fn add(&mut self, other: &IndexStats) {
+        self.entries += other.entries;
+        self.nodes += other.nodes;
+        self.segments += other.segments;
+        self.path_bytes += other.path_bytes;
+        self.entry_bytes += other.entry_bytes;
+        self.bucket_bytes += other.bucket_bytes;
+        self.trigram_bytes += other.trigram_bytes;
+        self.lookup_bytes += other.lookup_bytes;
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        return self.path_bytes + self.entry_bytes + self.bucket_bytes + self.trigram_bytes + self.lookup_bytes;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    segments: Vec<Arc<Segment>>,
+    tree: PathTable,
+    // the interning map stays with the builder, this is only its size for the stats
+    lookup_bytes: usize,
+    // deepest entry, the hash search tries every depth up to it
+    pub stack: u16,
+    // bumped by every publish, so a search knows when its results are stale
+    pub generation: u64,
+    pub root: Arc<Root>,
+}
+
+impl Snapshot {
+    /// The segments in order. Searches go through them one at a time so they can show what they
+    /// have so far and stop early.
+    pub fn segments(&self) -> &[Arc<Segment>] {
+        return &self.segments;
+    }
+
+    /// Live entries, what the status line counts.
+    pub fn entry_count(&self) -> usize {
+        return self.segments.iter().map(|segment| segment.live).sum();
+    }
+
+    pub fn bucket(&self, hash: u64) -> impl ParallelIterator<Item = &Entry> {
+        return self.segments.par_iter().flat_map_iter(move |segment| segment.bucket(hash));
+    }
+
+    /// Clears `out` and fills it with the entry's path text.
+    pub fn write_text(&self, entry: &Entry, out: &mut String) {
+        out.clear();
+        self.tree.write_text(entry.node, out);
+    }
+
+    pub fn path(&self, entry: &Entry) -> Arc<EntryPath> {
+        return Arc::new(self.tree.path(entry.node));
+    }
+
+    pub fn stats(&self) -> IndexStats {
+        let mut stats = IndexStats {
+            nodes: self.tree.len(),
+            segments: self.segments.len(),
+            path_bytes: self.tree.bytes(),
+            lookup_bytes: self.lookup_bytes,
+            ..IndexStats::default()
+        };
+        for segment in &self.segments {
+            let (entries, buckets, trigrams) = segment.bytes();
+            stats.entries += segment.live;
+            stats.entry_bytes += entries;
+            stats.bucket_bytes += buckets;
+            stats.trigram_bytes += trigrams;
+        }
+        return stats;
+    }
+}
+
+fn intersect_sorted(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            i += 1;
+        } else if a[i] > b[j] {
+            j += 1;
+        } else {
+            out.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    return out;
+}
+
+/// The index thread's side. Changes pile up here and nobody else sees them until publish().
+#[derive(Default)]
+pub struct IndexBuilder {
+    root: Arc<Root>,
+    tree: PathTree,
+    sealed: Vec<Arc<Segment>>,
+    pending: Vec<Entry>,
+    generation: u64,
+    changed: bool,
+}
+
+impl IndexBuilder {
+    pub fn new() -> IndexBuilder {
+        return IndexBuilder::default();
+    }
+
+    pub fn rooted(self, root: Root) -> IndexBuilder {
+        return IndexBuilder { root: Arc::new(root), ..self };
+    }
+
+    pub fn push(&mut self, path: &EntryPath, depth: u16, meta: Option<EntryMeta>) {
+        let node = self.tree.intern(path);
+        self.pending.push(Entry { node, depth, meta });
+        self.changed = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.sealed.clear();
+        self.pending.clear();
+        self.changed = true;
+    }
+
+    pub fn contains(&self, path: &EntryPath, stack: u16) -> bool {
+        let Some(node) = self.tree.lookup(path) else {
+            return false;
+        };
+        return self.pending.iter().any(|entry| entry.node == node)
+            || self.sealed.iter().any(|segment| segment.position(node, path.file_name(), stack).is_some());
+    }
+
+    pub fn update_meta(&mut self, path: &EntryPath, stack: u16, meta: Option<EntryMeta>) {
+        let Some(node) = self.tree.lookup(path) else {
+            return;
+        };
+        if let Some(entry) = self.pending.iter_mut().find(|entry| entry.node == node) {
+            entry.meta = meta;
+            self.changed = true;
+            return;
+        }
+        for segment in self.sealed.iter_mut() {
+            if let Some(id) = segment.position(node, path.file_name(), stack) {
+                *segment = Arc::new(segment.edited(|entries| {
+                    if let Some(entry) = entries[id].as_mut() {
+                        entry.meta = meta;
+                    }
+                }));
+                self.changed = true;
+                return;
+            }
+        }
+    }
+
+    // drops a path and, when it was a directory, everything underneath it
+    pub fn remove_tree(&mut self, path: &EntryPath) {
+        // the node stays in the tree, a path that comes back just gets the same id again
+        let Some(node) = self.tree.lookup(path) else {
+            return;
+        };
+        let tree = &self.tree;
+        let doomed = |entry: &Entry| tree.is_under(entry.node, node);
+        let before = self.pending.len();
+        self.pending.retain(|entry| !doomed(entry));
+        let mut changed = self.pending.len() != before;
+        let edits: Vec<Option<Arc<Segment>>> = self.sealed.par_iter()
+            .map(|segment| {
+                if !segment.live().any(doomed) {
+                    return None;
+                }
+                return Some(Arc::new(segment.edited(|entries| {
+                    for slot in entries.iter_mut() {
+                        if slot.as_ref().is_some_and(doomed) {
+                            *slot = None;
+                        }
+                    }
+                })));
+            })
+            .collect();
+        for (segment, edit) in self.sealed.iter_mut().zip(edits) {
+            if let Some(edit) = edit {
+                *segment = edit;
+                changed = true;
+            }
+        }
+        self.changed |= changed;
+    }
+
+    /// Builds whatever changed, full segments in parallel, and swaps the result in for readers.
+    /// False when there was nothing to publish.
+    pub fn publish(&mut self, index: &SharedIndex) -> bool {
+        if !self.changed {
+            return false;
+        }
+        let tree = self.tree.freeze();
+        let full = self.pending.len() / SEGMENT_SIZE * SEGMENT_SIZE;
+        if full > 0 {
+            let sealed: Vec<Arc<Segment>> = self.pending[..full].par_chunks(SEGMENT_SIZE)
+                .map(|chunk| Arc::new(Segment::build(chunk, &tree)))
+                .collect();
+            self.sealed.extend(sealed);
+            self.pending.drain(..full);
+        }
+        self.sealed.par_iter_mut()
+            .filter(|segment| segment.is_sparse())
+            .for_each(|segment| *segment = Arc::new(segment.compacted(&tree)));
+        let mut segments = self.sealed.clone();
+        if !self.pending.is_empty() {
+            segments.push(Arc::new(Segment::build(&self.pending, &tree)));
+        }
+        self.generation += 1;
+        let stack = segments.iter().map(|segment| segment.stack).max().unwrap_or_default();
+        index.store(Arc::new(Snapshot {
+            segments,
+            tree,
+            lookup_bytes: self.tree.lookup_bytes(),
+            stack,
+            generation: self.generation,
+            root: self.root.clone(),
+        }));
+        self.changed = false;
+        return true;
+    }
+}
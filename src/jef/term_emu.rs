@@ -1,16 +1,18 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::{
     error::Error,
     io,
+    ops::Range,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
@@ -18,15 +20,27 @@ use tui::{
 };
 
 use std::sync::{Arc, Mutex};
+use crossbeam_channel::Sender;
 
 use crate::jef::{
-    opener::{open, returning_terminal_at, open_terminal},
-    flags::Flag,
+    opener::{open, open_at_line, returning_terminal_at, open_terminal},
+    grep::parse_hit,
+    highlight::fit,
+    query::QueryError,
+    frecency::{self, SharedFrecency},
+    control::{Control, Controller},
+    indexer::{IndexProgress, Listing, SharedList, SharedProgress, PAGE_SIZE},
+    flags::SearchMode,
+    path::EntryPath,
+    roots::{project_root, RootScope},
+    snapshot::{IndexStats, SharedScope},
+    opener::Config,
 };
 
 use super::opener::special_open;
 
-type SharedList = Arc<Mutex<Vec<Arc<String>>>>;
+// how close to the end of the list the selection gets before the next page is asked for
+const PAGE_AHEAD: usize = PAGE_SIZE / 10;
 
 macro_rules! write_bar {
     ($var:ident, $to_write:expr) => {
@@ -42,6 +56,10 @@ macro_rules! write_bar {
 struct StatefulList {
     state: ListState,
     items: SharedList,
+    // the search the selection was made in
+    generation: u64,
+    // the first row on screen, see window
+    top: usize,
 }
 
 impl StatefulList {
@@ -49,24 +67,63 @@ impl StatefulList {
         StatefulList {
             state: ListState::default(),
             items,
+            generation: 0,
+            top: 0,
         }
     }
 
+    // the rows that fit in `height`, scrolled only as far as it takes to keep the selection in
+    // view like the List widget would, and the state to draw just those rows with. The widgets
+    // only ever build the rows on screen however many the list holds
+    fn window(&mut self, height: usize) -> (Range<usize>, ListState) {
+        let selected = self.state.selected().unwrap_or_default();
+        let height = height.max(1);
+        if selected < self.top {
+            self.top = selected;
+        } else if selected >= self.top + height {
+            self.top = selected + 1 - height;
+        }
+        let mut state = ListState::default();
+        state.select(Some(selected - self.top));
+        return (self.top..self.top + height, state);
+    }
+
+    // whether the search found more than the list holds so far
+    fn has_more(&self) -> bool {
+        if let Ok(items) = self.items.lock() {
+            return items.more > 0;
+        };
+        return false;
+    }
+
     // returns 0 if lock fails - use with caution
     fn quick_ref_items_len(&self) -> isize {
         let items = self.items.clone();
         if let Ok(items) = items.lock() {
-            return items.len() as isize;            
+            return items.paths.len() as isize;            
         } else {
             return 0;
         };
     }
 
+    // results of a new search start at the top, more of the same search leave the selection be
+    fn follow_search(&mut self) {
+        let mut generation = self.generation;
+        if let Ok(items) = self.items.lock() {
+            generation = items.generation;
+        };
+        if generation != self.generation {
+            self.generation = generation;
+            self.state.select(Some(0));
+        }
+    }
+
     fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= (self.quick_ref_items_len() - 1) as usize {
-                    0
+                    // the next page is on its way, see page_results
+                    if self.has_more() { i } else { 0 }
                 } else {
                     i + 1
                 }
@@ -98,6 +155,8 @@ impl StatefulList {
 ///
 /// Check the event handling at the bottom to see how to change the state on incoming events.
 /// Check the drawing logic for items on how to specify the highlighting style for selected items.
+// Visual is handled everywhere but nothing switches to it yet
+#[allow(dead_code)]
 enum AppState {
     Fuzzy,
     FuzzyNorm,
@@ -111,22 +170,57 @@ enum AppState {
     Exit,
 }
 struct App {
-    flag: Arc<Mutex<Flag>>,
+    controller: Controller,
+    // straight to the search thread, for the next page of results
+    pager: Sender<Control>,
+    // the search and list length the last page was asked for at
+    paged: Option<(u64, usize)>,
+    // what the workers were last told, so only real changes wake them
+    sent_search: (String, SearchMode),
+    sent_dir: PathBuf,
     items: StatefulList,
     browser_items: StatefulList,
     search_term: Arc<Mutex<String>>,
+    search_mode: Arc<Mutex<SearchMode>>,
+    show_ignored: Arc<Mutex<bool>>,
+    finder_error: QueryError,
+    browser_error: QueryError,
+    frecency: SharedFrecency,
+    index: SharedScope,
+    // which trees the finder covers, see `:root`
+    root: RootScope,
+    // the project around the current directory, for the finder's title
+    project: Option<PathBuf>,
+    progress: SharedProgress,
+    // shown in place of the mode in the status bar until the next key press
+    message: Option<String>,
     app_state: AppState,
     cmd: String,
     last_char: Option<char>,
 }
 
 impl App {
-    fn from(flag: Arc<Mutex<Flag>>, items: SharedList, browser_paths: SharedList, search_term: Arc<Mutex<String>>) -> App {
+    #[allow(clippy::too_many_arguments)]
+    fn from(controller: Controller, pager: Sender<Control>, items: SharedList, browser_paths: SharedList, search_term: Arc<Mutex<String>>, search_mode: Arc<Mutex<SearchMode>>, show_ignored: Arc<Mutex<bool>>, finder_error: QueryError, browser_error: QueryError, frecency: SharedFrecency, index: SharedScope, progress: SharedProgress) -> App {
         App {
-            flag,
+            controller,
+            pager,
+            paged: None,
+            sent_search: (String::new(), SearchMode::Fuzzy),
+            sent_dir: std::env::current_dir().unwrap_or_default(),
             items: StatefulList::with_items(items),
             browser_items: StatefulList::with_items(browser_paths),
             search_term,
+            search_mode,
+            show_ignored,
+            finder_error,
+            browser_error,
+            frecency,
+            index,
+            root: RootScope::Project,
+            project: find_project(&std::env::current_dir().unwrap_or_default()),
+            progress,
+            message: None,
             app_state: AppState::Normal,
             cmd: String::new(),
             last_char: None,
@@ -137,7 +231,7 @@ impl App {
         let items = self.browser_items.items.clone();
         let selected = self.browser_items.state.selected().unwrap_or_default();
         if let Ok(items) = items.lock(){ 
-            if let Some(item) = items.get(selected){
+            if let Some(item) = items.paths.get(selected){
                 let item = &*item.clone();
                 self.check_and_open(terminal, item);
             }
@@ -148,22 +242,109 @@ impl App {
         let items = self.items.items.clone();
         let selected = self.items.state.selected().unwrap_or_default();
         if let Ok(items) = items.lock(){ 
-            if let Some(item) = items.get(selected){
+            if let Some(item) = items.paths.get(selected){
                 let item = &*item.clone();
                 self.check_and_open(terminal, item);
             }
         };
     }
 
-    fn check_and_open<B: Backend>(&mut self, terminal:&mut Terminal<B>, item: &String) {
-        if let Ok(metadata) = std::fs::metadata(item){
+    fn check_and_open<B: Backend>(&mut self, terminal:&mut Terminal<B>, item: &EntryPath) {
+        if let Some((path, line)) = parse_hit(item) {
             self.app_state = AppState::Normal;
+            self.remember_visit(path);
+            self.handing_over(|| open_at_line(terminal, path, line));
+        } else if let Ok(metadata) = std::fs::metadata(item.as_path()){
+            self.app_state = AppState::Normal;
+            self.remember_visit(item.as_path());
             if metadata.is_dir(){
-                std::env::set_current_dir(item).unwrap();
+                std::env::set_current_dir(item.as_path()).unwrap();
             }
             if metadata.is_file() {
-                open(terminal, item.clone());
+                self.handing_over(|| open(terminal, item.as_path()));
+            }
+        }
+    }
+
+    // the workers hold still while another program has the terminal
+    fn handing_over(&self, run: impl FnOnce()) {
+        self.controller.send(Control::Pause);
+        run();
+        self.controller.send(Control::Resume);
+    }
+
+    // tells the workers whatever the last key changed, they sleep until something does
+    fn sync_workers(&mut self) {
+        let mut search = self.sent_search.clone();
+        if let Ok(term) = self.search_term.lock() {
+            search.0 = term.clone();
+        };
+        if let Ok(mode) = self.search_mode.lock() {
+            search.1 = *mode;
+        };
+        if search != self.sent_search {
+            self.controller.send(Control::SearchChanged { term: search.0.clone(), mode: search.1 });
+            self.sent_search = search;
+        }
+        let dir = std::env::current_dir().unwrap_or_default();
+        if dir != self.sent_dir {
+            self.project = find_project(&dir);
+            self.controller.send(Control::DirChanged(dir.clone()));
+            self.sent_dir = dir;
+        }
+    }
+
+    fn remember_visit(&mut self, path: &std::path::Path) {
+        if let Ok(mut frecency) = self.frecency.lock() {
+            frecency.visit(path, frecency::now());
+            frecency.save();
+        };
+    }
+
+    // `:z words` changes to the best remembered directory matching the words
+    fn jump(&mut self, query: &str) {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let mut best = None;
+        if let Ok(mut frecency) = self.frecency.lock() {
+            best = frecency.best_dir(query, &current_dir, frecency::now());
+        };
+        match best {
+            Some(dir) if std::env::set_current_dir(&dir).is_ok() => {
+                self.remember_visit(&dir);
+                reset_selection(self);
+            },
+            _ => self.message = Some(format!("z: nothing remembered matches '{}'", query)),
+        }
+    }
+
+    // `:root name` points the finder at a root from jef.toml, `:root all` at every one of them,
+    // `:root .` at the current directory and `:root` back at the project
+    fn switch_root(&mut self, arg: &str) {
+        match RootScope::parse(arg, &Config::default_config().roots) {
+            Ok(root) => self.set_root(root),
+            Err(message) => self.message = Some(format!("root: {}", message)),
+        }
+    }
+
+    fn set_root(&mut self, root: RootScope) {
+        self.controller.send(Control::RootChanged(root.clone()));
+        self.root = root;
+        reset_selection(self);
+    }
+
+    // asks for the next page of results while the selection is still a screen or so from the end
+    // of what the list holds, so scrolling never runs into it
+    fn page_results(&mut self) {
+        let selected = self.items.state.selected().unwrap_or_default();
+        let mut wanted = None;
+        if let Ok(items) = self.items.items.lock() {
+            if items.more > 0 && selected + PAGE_AHEAD >= items.paths.len() {
+                wanted = Some((items.generation, items.paths.len()));
             }
+        };
+        if let Some(wanted) = wanted.filter(|wanted| Some(*wanted) != self.paged) {
+            let _ = self.pager.send(Control::MoreResults(wanted.0));
+            self.paged = Some(wanted);
         }
     }
 
@@ -173,7 +354,12 @@ impl App {
     }
 }
 
-pub fn explorer(flag: Arc<Mutex<Flag>>, paths: SharedList, browser_paths: SharedList, search_term: Arc<Mutex<String>>) -> Result<(), Box<dyn Error>> {
+fn find_project(dir: &Path) -> Option<PathBuf> {
+    return project_root(dir, &Config::default_config().indexer.project_markers);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn explorer(controller: Controller, pager: Sender<Control>, paths: SharedList, browser_paths: SharedList, search_term: Arc<Mutex<String>>, search_mode: Arc<Mutex<SearchMode>>, show_ignored: Arc<Mutex<bool>>, finder_error: QueryError, browser_error: QueryError, frecency: SharedFrecency, index: SharedScope, progress: SharedProgress) -> Result<(), Box<dyn Error>> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -183,7 +369,7 @@ pub fn explorer(flag: Arc<Mutex<Flag>>, paths: SharedList, browser_paths: Shared
 
     // create app and run it
     let tick_rate = Duration::from_millis(100);
-    let app = App::from(flag, paths, browser_paths, search_term);
+    let app = App::from(controller, pager, paths, browser_paths, search_term, search_mode, show_ignored, finder_error, browser_error, frecency, index, progress);
     let res = run_app(&mut terminal, app, tick_rate);
 
     // restore terminal
@@ -217,6 +403,7 @@ fn run_app<B: Backend>(
             .unwrap_or_else(|| Duration::from_secs(0));
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
+                app.message = None;
                 match app.app_state {
                     AppState::Fuzzy  => {handle_key_fuzzy(&mut app, key)},
                     AppState::FuzzyNorm => {handle_key_normal(terminal, &mut app, key)},
@@ -226,9 +413,10 @@ fn run_app<B: Backend>(
                     AppState::Visual => {},
                     AppState::Command => {handle_key_cmd(terminal, &mut app, key)},
                     AppState::Shell => {handle_key_cmd(terminal, &mut app, key)},
-                    AppState::Message => {handle_key_normal(terminal, &mut app, key)}
+                    AppState::Message => {app.app_state = AppState::Normal},
                     AppState::Exit => {break},
                 }
+                app.sync_workers();
             }
         }
 
@@ -300,6 +488,12 @@ fn handle_key_fuzzy(app: &mut App, key: KeyEvent) {
             reset_selection(app);
             app.app_state = AppState::FuzzyNorm;
         },
+        KeyCode::Tab => {
+            if let Ok(mut search_mode) = app.search_mode.lock() {
+                *search_mode = search_mode.next();
+            };
+            reset_selection(app);
+        },
 
         KeyCode::Up => app.items.next(),
         KeyCode::Down => app.items.previous(),
@@ -358,18 +552,26 @@ fn handle_key_normal<B: Backend>(terminal: &mut Terminal<B>, app: &mut App, key:
             app.app_state = AppState::Shell;
         },
         KeyCode::Char('$') => {
-            special_open(terminal);
+            app.handing_over(|| special_open(terminal));
         },
         KeyCode::Char('#') => {
-            open_terminal(terminal);
+            app.handing_over(|| open_terminal(terminal));
         },
         KeyCode::Char('/') => {
             app.app_state = AppState::Match;
         },
-        KeyCode::Char(c) => {
-            if !parse_cmd_num(app, c) {
-                app.cmd.clear();
-            }
+        KeyCode::Char('I') => {
+            if let Ok(mut show_ignored) = app.show_ignored.lock() {
+                *show_ignored = !*show_ignored;
+            };
+            app.controller.send(Control::FilterChanged);
+        },
+        KeyCode::Char('P') => {
+            let root = app.root.toggle_project();
+            app.set_root(root);
+        },
+        KeyCode::Char(c) if !parse_cmd_num(app, c) => {
+            app.cmd.clear();
         },
         KeyCode::Enter => {
             match app.cmd.parse::<i32>() {
@@ -418,11 +620,6 @@ fn parse_cmd_num(app: &mut App, c: char) -> bool {
     }
 }
 fn handle_key_cmd<B: Backend>(terminal:&mut Terminal<B>, app: &mut App, key: KeyEvent) {
-    let mut ctrl = false;
-    match key.modifiers {
-        KeyModifiers::CONTROL => {ctrl = true;},
-        _ => {},
-    }
     match key.code {
         KeyCode::Esc => {
             app.app_state = AppState::Normal;
@@ -454,16 +651,31 @@ fn handle_key_cmd<B: Backend>(terminal:&mut Terminal<B>, app: &mut App, key: Key
 }
 
 fn handle_shell<B: Backend>(terminal:&mut Terminal<B>, app: &mut App) {
-    returning_terminal_at(terminal, &app.cmd);
+    app.handing_over(|| returning_terminal_at(terminal, &app.cmd));
     app.app_state = AppState::Normal;
 }
 
 fn handle_cmd(app: &mut App) {
-    match &*app.cmd {
+    let cmd = app.cmd.clone();
+    let (name, args) = cmd.split_once(' ').unwrap_or((&cmd, ""));
+    match name {
         "wq" => {app.app_state = AppState::Exit},
         "q" => {app.app_state = AppState::Exit},
         "q!" => {app.app_state = AppState::Exit},
         "debug" => {},
+        "z" => {
+            app.app_state = AppState::Normal;
+            app.jump(args.trim());
+        },
+        "root" => {
+            app.app_state = AppState::Normal;
+            app.switch_root(args);
+        },
+        "stats" => {app.app_state = AppState::Message},
+        "index" => {
+            app.controller.send(Control::Reindex);
+            app.app_state = AppState::Message;
+        },
         _ => {app.app_state = AppState::Normal},
     }
     app.cmd.clear();
@@ -480,17 +692,13 @@ fn normal_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
 
     let mut items: Vec<ListItem> = Vec::new();
     let shared_items = app.browser_items.items.clone();
-    let i = match app.browser_items.state.selected() {
-        Some(i) => {
-            i
-        }
-        None => 0,
-    };
-    let mut i = -1 * (i as isize);
+    let i = app.browser_items.state.selected().unwrap_or_default();
+    // the borders take a row each
+    let (rows, mut state) = app.browser_items.window(chunks[0].height.saturating_sub(2) as usize);
+    let mut i = rows.start as isize - i as isize;
     if let Ok(shared_items) = shared_items.lock() {        
-        for item in shared_items.clone(){
-            let lines = &*item.clone();
-            let mut start: String;
+        for item in shared_items.paths.iter().skip(rows.start).take(rows.len()) {
+            let start: String;
             if i == 0 {
                 start = format!("{}  ",app.browser_items.state.selected().unwrap()).to_string();
             } else if i.abs() < 10 {
@@ -498,8 +706,7 @@ fn normal_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
             } else {
                 start = format!(" {} ",i.abs()).to_string();
             }
-            start.push_str(&lines);
-            let lines = vec![Spans::from(start)];
+            let lines = vec![path_row(start, item, &shared_items, chunks[0])];
             items.push(ListItem::new(lines).style(Style::default().fg(Color::White).bg(Color::Black)));
             i += 1;
         }
@@ -521,16 +728,26 @@ fn normal_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
     let mut text = Vec::new();
     match app.app_state {
         AppState::Normal => {
-            write_bar!(text, format!("NORMAL"));
+            let mut show_ignored = false;
+            if let Ok(shared) = app.show_ignored.lock(){
+                show_ignored = *shared;
+            };
+            if let Some(message) = &app.message {
+                write_bar!(text, message.clone());
+            } else if show_ignored {
+                write_bar!(text, format!("NORMAL [+ignored]{}", index_status(&app.progress)));
+            } else {
+                write_bar!(text, format!("NORMAL{}", index_status(&app.progress)));
+            }
         },
         AppState::Match => {
             if let Ok(search_term) = app.search_term.lock(){
-                write_bar!(text, format!("/{}", &search_term));
+                write_bar!(text, format!("/{}{}", &search_term, error_suffix(&app.browser_error)));
             };
         },
         AppState::MatchNorm => {
             if let Ok(search_term) = app.search_term.lock(){
-                write_bar!(text, format!("/{}", &search_term));
+                write_bar!(text, format!("/{}{}", &search_term, error_suffix(&app.browser_error)));
             };
         },
         AppState::Command => {
@@ -546,38 +763,63 @@ fn normal_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
         .style(Style::default().fg(Color::White).bg(Color::Black));
     // We can now render the item list
 
-    f.render_stateful_widget(items, chunks[0], &mut app.browser_items.state);
+    f.render_stateful_widget(items, chunks[0], &mut state);
     f.render_widget(label, chunks[1]);
 }
 
 
-fn status_bar<B: Backend>(terminal: Terminal<B>, app: &mut App) -> Vec<Spans<'static>>{
-    let mut text = Vec::new();
-    match app.app_state {
-        AppState::Normal => {
-            write_bar!(text, format!(""));
-        },
-        AppState::Match => {
-            if let Ok(search_term) = app.search_term.lock(){
-                write_bar!(text, format!("/{}", &search_term));
-            };
-        },
-        AppState::MatchNorm => {
-            if let Ok(search_term) = app.search_term.lock(){
-                write_bar!(text, format!("/{}", &search_term));
-            };
-        },
-        AppState::Command => {
-            write_bar!(text, format!(":{}",app.cmd));
-        },
-        AppState::Shell => {
-            write_bar!(text, format!("!{}",app.cmd));
-        },
-        _ => {},
+// one row of either list: the path from below its root, the root's name in front when it has one
+// and the matches picked out, cut to fit around them
+fn path_row<'a>(start: String, path: &EntryPath, listing: &Listing, area: Rect) -> Spans<'a> {
+    let from = listing.offset(path);
+    let label = listing.root_of(path).and_then(|root| root.label()).map(|label| format!("[{}] ", label)).unwrap_or_default();
+    // the borders and the ">>" in front of the selection
+    let width = (area.width as usize).saturating_sub(4 + start.chars().count() + label.chars().count());
+    let matched = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let mut spans = vec![Span::raw(start), Span::styled(label, Style::default().fg(Color::DarkGray))];
+    for (run, is_match) in fit(&path[from..], &listing.highlight.ranges(path, from), width) {
+        spans.push(match is_match {
+            true => Span::styled(run, matched),
+            false => Span::raw(run),
+        });
+    }
+    return Spans::from(spans);
+}
+
+// a query that does not compile says why next to the search term instead of just listing nothing
+fn error_suffix(query_error: &QueryError) -> String {
+    if let Ok(query_error) = query_error.lock() {
+        if let Some(message) = &*query_error {
+            return format!("  [{}]", message);
+        }
     }
-    return text;
+    return String::new();
 }
- 
+
+// how far the search for the current term has got, gone once it is done
+fn progress_suffix(list: &SharedList) -> String {
+    if let Ok(list) = list.lock() {
+        if list.is_searching() {
+            return format!("  [{}/{}]", list.scanned, list.total);
+        }
+    }
+    return String::new();
+}
+
+// whether an empty finder means nothing matched or nothing is indexed yet
+fn index_status(progress: &SharedProgress) -> String {
+    if let Ok(progress) = progress.lock() {
+        let elapsed = progress.elapsed().as_secs_f64();
+        if progress.is_done() {
+            return format!("  [indexed {} in {:.1}s ✓]", progress.entries, elapsed);
+        }
+        if progress.started.is_some() {
+            return format!("  [indexing {}, {} dirs pending, {:.1}s]", progress.entries, progress.pending_dirs, elapsed);
+        }
+    }
+    return String::new();
+}
+
 fn fuzzy_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
     let height = f.size().height as u32;
     let _width = f.size().width as u32;
@@ -588,41 +830,49 @@ fn fuzzy_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
         .split(f.size());
 
     let mut items: Vec<ListItem> = Vec::new();
+    app.items.follow_search();
+    app.page_results();
     let shared_items = app.items.items.clone();
-    let i = match app.items.state.selected() {
-        Some(i) => {
-            i
-        }
-        None => 0,
-    };
-    let mut i = -1 * (i as isize);
+    let i = app.items.state.selected().unwrap_or_default();
+    let (rows, mut state) = app.items.window(chunks[0].height.saturating_sub(2) as usize);
+    let mut i = rows.start as isize - i as isize;
     if let Ok(shared_items) = shared_items.lock() {        
-        for item in shared_items.clone(){
-            let lines = &*item.clone();
-            let mut start: String;
-            if i.abs() < 10 {
-                start = format!("{}  ",i.abs()).to_string();
+        for item in shared_items.paths.iter().skip(rows.start).take(rows.len()) {
+            let start = if i.abs() < 10 {
+                format!("{}  ",i.abs()).to_string()
             } else {
-                start = format!("{} ",i.abs()).to_string();
-            }
-            start.push_str(&lines);
-            let lines = vec![Spans::from(start)];
+                format!("{} ",i.abs()).to_string()
+            };
+            let lines = vec![path_row(start, item, &shared_items, chunks[0])];
             items.push(ListItem::new(lines).style(Style::default().fg(Color::White).bg(Color::Black)));
             i += 1;
         }
+        if shared_items.more > 0 && rows.end >= shared_items.paths.len() {
+            let more = Spans::from(format!("   {} more…", shared_items.more));
+            items.push(ListItem::new(vec![more]).style(Style::default().fg(Color::DarkGray).bg(Color::Black)));
+        }
     };
 
     // Create a List from all list items and highlight the currently selected one
     let mut text = vec![
         Spans::from(vec![
-            Span::raw(format!("")),
+            Span::raw(String::new()),
         ]),
     ];
+    let mut label = SearchMode::Fuzzy.label();
+    if let Ok(search_mode) = app.search_mode.lock(){
+        label = search_mode.label();
+    };
     if let Ok(search_term) = app.search_term.lock(){
-        write_bar!(text, format!("FIND:{}", &search_term));
+        write_bar!(text, format!("{}:{}{}{}{}", label, &search_term, error_suffix(&app.finder_error), progress_suffix(&app.items.items), index_status(&app.progress)));
     };
     let current_dir = std::env::current_dir().unwrap_or_default();
-    let title = format!("| {:?} |", current_dir);
+    let title = match (&app.root, &app.project) {
+        (RootScope::Project, Some(project)) if *project != current_dir => format!("| project {:?} |", project),
+        (RootScope::Project | RootScope::CurrentDir, _) => format!("| {:?} |", current_dir),
+        (RootScope::Named(name), _) => format!("| root {} |", name),
+        (RootScope::All, _) => "| all roots |".to_string(),
+    };
     let items = List::new(items)
         .block(Block::default().borders(Borders::ALL).title(title))
         .highlight_style(
@@ -637,10 +887,61 @@ fn fuzzy_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
     // We can now render the item list
     
 
-    f.render_stateful_widget(items, chunks[0], &mut app.items.state);
+    f.render_stateful_widget(items, chunks[0], &mut state);
     f.render_widget(label, chunks[1]);
 }
 
+fn human_bytes(bytes: usize) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB"] {
+        if size < 1024.0 {
+            return format!("{:.1} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    return format!("{:.1} GiB", size);
+}
+
+// `:stats` and `:index`, redrawn every tick so it keeps up with an index that is still growing
+fn stats_widget<B: Backend> (f: &mut Frame<B>, app: &mut App) {
+    let mut stats = IndexStats::default();
+    for tree in app.index.load().iter() {
+        stats.add(&tree.load().stats());
+    }
+    let mut progress = IndexProgress::default();
+    if let Ok(shared) = app.progress.lock() {
+        progress = shared.clone();
+    };
+    let status = match progress.is_done() {
+        true => "done",
+        false => "indexing",
+    };
+    let rows = [
+        ("status", status.to_string()),
+        ("pending dirs", progress.pending_dirs.to_string()),
+        ("elapsed", format!("{:.1}s", progress.elapsed().as_secs_f64())),
+        ("", String::new()),
+        ("entries", stats.entries.to_string()),
+        ("path nodes", stats.nodes.to_string()),
+        ("segments", stats.segments.to_string()),
+        ("", String::new()),
+        ("path tree", human_bytes(stats.path_bytes)),
+        ("path lookup", human_bytes(stats.lookup_bytes)),
+        ("entries", human_bytes(stats.entry_bytes)),
+        ("name buckets", human_bytes(stats.bucket_bytes)),
+        ("trigrams", human_bytes(stats.trigram_bytes)),
+        ("total", human_bytes(stats.total_bytes())),
+        ("", String::new()),
+        ("", "press any key".to_string()),
+    ];
+    let text: Vec<Spans> = rows.into_iter()
+        .map(|(name, value)| Spans::from(format!("{:<14}{}", name, value)))
+        .collect();
+    let stats = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("| index |"))
+        .style(Style::default().fg(Color::White).bg(Color::Black));
+    f.render_widget(stats, f.size());
+}
 
 fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     match app.app_state {
@@ -649,7 +950,7 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         AppState::Match   => {normal_widget(f, app)},
         AppState::MatchNorm => {normal_widget(f, app)},
         AppState::Normal  => {normal_widget(f, app)},
-        AppState::Message => {},
+        AppState::Message => stats_widget(f, app),
         AppState::Visual  => {},
         AppState::Command => {normal_widget(f, app)},
         AppState::Shell => {normal_widget(f, app)},
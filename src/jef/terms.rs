@@ -0,0 +1,148 @@
+/*
+ * fzf's extended search syntax for the name part of a query, in both finders. Terms are split on
+ * spaces and every one of them has to match, in any order. A plain term is left to the finder's own
+ * matcher, fuzzy for `f` and a name prefix for `/`. The others compare the folded path from where
+ * the search started, "./" dropped:
+ *
+ *   'term    contains term exactly
+ *   ^term    starts with term
+ *   term$    ends with term, ^term$ is the whole path
+ *   !term    does not contain term, and !^term / !term$ the same for the anchors
+ *
+ * A term that is nothing but operators is dropped. Only the plain terms score, the others just
+ * decide whether a path is in or out.
+ */
+
+use std::ops::Range;
+use crate::jef::unicode::{fold, fold_spans};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Plain,
+    Exact,
+    Prefix,
+    Suffix,
+    Equal,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    kind: Kind,
+    negated: bool,
+    // folded the same way as the paths it is compared with
+    text: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Terms {
+    terms: Vec<Term>,
+    ignore_diacritics: bool,
+}
+
+impl Terms {
+    pub fn parse(query: &str, ignore_diacritics: bool) -> Terms {
+        let mut terms = Vec::new();
+        for token in query.split_whitespace() {
+            let (negated, token) = match token.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, token),
+            };
+            let (exact, token) = match token.strip_prefix('\'') {
+                Some(rest) => (true, rest),
+                None => (false, token),
+            };
+            let (prefix, token) = match token.strip_prefix('^') {
+                Some(rest) => (true, rest),
+                None => (false, token),
+            };
+            let (suffix, token) = match token.strip_suffix('$') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, token),
+            };
+            if token.is_empty() {
+                continue;
+            }
+            let kind = match (prefix, suffix) {
+                (true, true) => Kind::Equal,
+                (true, false) => Kind::Prefix,
+                (false, true) => Kind::Suffix,
+                // fzf reads a negated term exactly too, a fuzzy "not" would rule out nearly everything
+                _ if exact || negated => Kind::Exact,
+                _ => Kind::Plain,
+            };
+            terms.push(Term { kind, negated, text: fold(token, ignore_diacritics).into_owned() });
+        }
+        return Terms { terms, ignore_diacritics };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.terms.is_empty();
+    }
+
+    /// The first term a path has to match with the finder's own matcher, the one worth looking up
+    /// in the name hashes before scanning everything.
+    pub fn lookup(&self) -> Option<&str> {
+        return self.terms.iter()
+            .find(|term| term.kind == Kind::Plain && !term.negated)
+            .map(|term| term.text.as_str());
+    }
+
+    /// The plain terms, folded, for a matcher that prepares them once per search.
+    pub fn plain(&self) -> impl Iterator<Item = &str> {
+        return self.terms.iter().filter(|term| term.kind == Kind::Plain).map(|term| term.text.as_str());
+    }
+
+    /// Whether `path` passes every term, with the summed score of the plain ones. `plain` scores
+    /// one plain term against the path the way the finder matches, `None` for no match.
+    pub fn score(&self, path: &str, plain: impl Fn(&str, &str) -> Option<i64>) -> Option<i64> {
+        if self.terms.is_empty() {
+            return None;
+        }
+        let relative = path.strip_prefix("./").unwrap_or(path);
+        // only folded when an exact or anchored term needs it
+        let mut folded = None;
+        let mut total = 0;
+        for term in &self.terms {
+            if term.kind == Kind::Plain {
+                total += plain(path, &term.text)?;
+                continue;
+            }
+            let folded = folded.get_or_insert_with(|| fold(relative, self.ignore_diacritics));
+            let found = match term.kind {
+                Kind::Exact | Kind::Plain => folded.contains(&term.text),
+                Kind::Prefix => folded.starts_with(&term.text),
+                Kind::Suffix => folded.ends_with(&term.text),
+                Kind::Equal => folded.as_ref() == term.text,
+            };
+            if found == term.negated {
+                return None;
+            }
+        }
+        return Some(total);
+    }
+
+    /// Where the terms matched a path that passed them, as byte ranges of `path`. `plain` finds
+    /// one plain term the way the finder matches it.
+    pub fn positions(&self, path: &str, plain: impl Fn(&str, &str) -> Option<Vec<Range<usize>>>) -> Vec<Range<usize>> {
+        let offset = if path.starts_with("./") { 2 } else { 0 };
+        let spans = fold_spans(&path[offset..], self.ignore_diacritics);
+        let mut ranges = Vec::new();
+        for term in self.terms.iter().filter(|term| !term.negated) {
+            if term.kind == Kind::Plain {
+                ranges.extend(plain(path, &term.text).unwrap_or_default());
+                continue;
+            }
+            let needle: Vec<char> = term.text.chars().collect();
+            let fits = |at: usize| spans[at..].iter().map(|span| span.1).take(needle.len()).eq(needle.iter().copied());
+            let at = match term.kind {
+                Kind::Prefix | Kind::Equal => Some(0).filter(|at| fits(*at)),
+                Kind::Suffix => spans.len().checked_sub(needle.len()).filter(|at| fits(*at)),
+                _ => (0..spans.len()).find(|at| fits(*at)),
+            };
+            if let Some(at) = at.filter(|_| !needle.is_empty()) {
+                ranges.push(offset + spans[at].2.start..offset + spans[at + needle.len() - 1].2.end);
+            }
+        }
+        return ranges;
+    }
+}
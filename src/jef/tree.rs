@@ -0,0 +1,191 @@
+/*
+ * Every path in the index stored once, as a tree. A node is one name plus the id of its parent,
+ * so "./src/jef/indexer.rs" costs a u32 and "indexer.rs" on top of the nodes "./src/jef" already
+ * needed, and the buckets and postings only ever hold u32 ids. Full paths are put back together
+ * on demand, into a reused buffer while searching and as an EntryPath for whatever gets shown.
+ *
+ * Names are kept as the bytes on disk, split at '/', so joining them again gives the exact path
+ * back, names that are not valid UTF-8 included. The index thread interns into a PathTree and
+ * hands searches a frozen PathTable. Nodes only ever get appended and live in fixed size chunks,
+ * so freezing shares every full chunk and copies just the last one.
+ */
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    collections::hash_map::{DefaultHasher, Entry},
+    ffi::OsStr,
+    hash::{Hash, Hasher},
+    mem::size_of,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    sync::Arc,
+};
+use crate::jef::path::{escape, EntryPath};
+
+pub const NO_PARENT: u32 = u32::MAX;
+const CHUNK_SIZE: usize = 1 << 12;
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    parent: u32,
+    name: Box<[u8]>,
+}
+
+/// The read only side, what a Snapshot carries.
+#[derive(Debug, Default, Clone)]
+pub struct PathTable {
+    chunks: Vec<Arc<[Node]>>,
+}
+
+impl PathTable {
+    fn node(&self, id: u32) -> &Node {
+        let id = id as usize;
+        return &self.chunks[id / CHUNK_SIZE][id % CHUNK_SIZE];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.chunks.iter().map(|chunk| chunk.len()).sum();
+    }
+
+    /// The last name of the path, escaped the same way EntryPath does it.
+    pub fn name(&self, id: u32) -> Cow<'_, str> {
+        return escape(OsStr::from_bytes(&self.node(id).name));
+    }
+
+    /// Appends the escaped text of the whole path, which is what matching and hashing work on.
+    pub fn write_text(&self, id: u32, out: &mut String) {
+        let node = self.node(id);
+        if node.parent != NO_PARENT {
+            self.write_text(node.parent, out);
+            out.push('/');
+        }
+        out.push_str(&escape(OsStr::from_bytes(&node.name)));
+    }
+
+    fn write_bytes(&self, id: u32, out: &mut Vec<u8>) {
+        let node = self.node(id);
+        if node.parent != NO_PARENT {
+            self.write_bytes(node.parent, out);
+            out.push(b'/');
+        }
+        out.extend_from_slice(&node.name);
+    }
+
+    pub fn path(&self, id: u32) -> EntryPath {
+        let mut bytes = Vec::new();
+        self.write_bytes(id, &mut bytes);
+        return EntryPath::new(Path::new(OsStr::from_bytes(&bytes)));
+    }
+
+    pub fn bytes(&self) -> usize {
+        return self.chunks.iter()
+            .flat_map(|chunk| chunk.iter())
+            .map(|node| size_of::<Node>() + node.name.len())
+            .sum();
+    }
+}
+
+/// The index thread's side, the only place nodes get added.
+#[derive(Debug, Default)]
+pub struct PathTree {
+    table: PathTable,
+    tail: Vec<Node>,
+    // keyed by a hash of the name so the name itself is not stored twice, the rare pair of names
+    // that share a hash under one parent goes to spill
+    children: HashMap<(u32, u64), u32>,
+    spill: HashMap<(u32, u64), Vec<u32>>,
+}
+
+fn name_hash(name: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    return hasher.finish();
+}
+
+impl PathTree {
+    fn node(&self, id: u32) -> &Node {
+        let sealed = self.table.chunks.len() * CHUNK_SIZE;
+        if (id as usize) < sealed {
+            return self.table.node(id);
+        }
+        return &self.tail[id as usize - sealed];
+    }
+
+    fn child(&self, parent: u32, name: &[u8]) -> Option<u32> {
+        let key = (parent, name_hash(name));
+        let found = self.children.get(&key)?;
+        if *self.node(*found).name == *name {
+            return Some(*found);
+        }
+        return self.spill.get(&key)?.iter().copied().find(|id| *self.node(*id).name == *name);
+    }
+
+    /// The id of `path`, adding whatever part of it the tree has not seen yet.
+    pub fn intern(&mut self, path: &EntryPath) -> u32 {
+        let mut parent = NO_PARENT;
+        for name in path.as_bytes().split(|b| *b == b'/') {
+            parent = match self.child(parent, name) {
+                Some(id) => id,
+                None => self.add(parent, name),
+            };
+        }
+        return parent;
+    }
+
+    /// The id of `path` if it was ever interned.
+    pub fn lookup(&self, path: &EntryPath) -> Option<u32> {
+        let mut parent = NO_PARENT;
+        for name in path.as_bytes().split(|b| *b == b'/') {
+            parent = self.child(parent, name)?;
+        }
+        return Some(parent);
+    }
+
+    fn add(&mut self, parent: u32, name: &[u8]) -> u32 {
+        let id = (self.table.chunks.len() * CHUNK_SIZE + self.tail.len()) as u32;
+        let key = (parent, name_hash(name));
+        match self.children.entry(key) {
+            Entry::Occupied(_) => self.spill.entry(key).or_default().push(id),
+            Entry::Vacant(slot) => {
+                slot.insert(id);
+            },
+        }
+        self.tail.push(Node { parent, name: name.into() });
+        if self.tail.len() == CHUNK_SIZE {
+            let full = std::mem::take(&mut self.tail);
+            self.table.chunks.push(Arc::from(full));
+        }
+        return id;
+    }
+
+    /// True for `ancestor` itself and everything below it.
+    pub fn is_under(&self, mut id: u32, ancestor: u32) -> bool {
+        while id != NO_PARENT {
+            if id == ancestor {
+                return true;
+            }
+            id = self.node(id).parent;
+        }
+        return false;
+    }
+
+    pub fn clear(&mut self) {
+        *self = PathTree::default();
+    }
+
+    pub fn freeze(&self) -> PathTable {
+        let mut table = self.table.clone();
+        if !self.tail.is_empty() {
+            table.chunks.push(Arc::from(self.tail.clone()));
+        }
+        return table;
+    }
+
+    /// What the interning map costs on top of the nodes themselves.
+    pub fn lookup_bytes(&self) -> usize {
+        let slot = size_of::<(u32, u64)>() + size_of::<u32>();
+        let spilled: usize = self.spill.values().map(|ids| slot + size_of::<Vec<u32>>() + ids.len() * size_of::<u32>()).sum();
+        return self.children.capacity() * slot + spilled;
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * One place that decides when two file names count as the same for matching. Every name is
+ * brought into one canonical form first, so an "é" typed as one code point and an "é" stored as
+ * "e" plus a combining accent (the way macOS writes file names) come out identical. With
+ * diacritics ignored that form is the decomposition with the combining marks dropped, so "resume"
+ * finds "résumé"; with them kept it is the composition, so an "é" stays one char that an "e"
+ * can't match. Then it is case folded, which unlike to_lowercase also turns "ß" into "ss" and a
+ * final sigma into a plain one. The hash and trigram indexes always store the diacritic free
+ * form, which makes them a superset of what either setting can match; the exact comparison
+ * afterwards decides.
+ */
+
+use std::{
+    borrow::Cow,
+    ops::Range,
+};
+use unicode_normalization::{
+    char::{decompose_canonical, is_combining_mark},
+    UnicodeNormalization,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+fn case_fold(c: char, out: &mut impl FnMut(char)) {
+    match c {
+        'ß' | 'ẞ' => {
+            out('s');
+            out('s');
+        },
+        'ς' => out('σ'),
+        _ => c.to_lowercase().for_each(out),
+    }
+}
+
+/// Calls `out(original, folded)` for every folded char of `s`. One char can fold into none (a
+/// dropped diacritic) or several ("ß" becomes "ss"), each of them comes with the char it came
+/// from so callers can still look at the real case and punctuation.
+pub fn fold_chars(s: &str, ignore_diacritics: bool, mut out: impl FnMut(char, char)) {
+    if s.is_ascii() {
+        s.chars().for_each(|c| out(c, c.to_ascii_lowercase()));
+        return;
+    }
+    if !ignore_diacritics {
+        s.nfc().for_each(|c| case_fold(c, &mut |f| out(c, f)));
+        return;
+    }
+    for c in s.chars() {
+        decompose_canonical(c, |d| {
+            if !is_combining_mark(d) {
+                case_fold(d, &mut |f| out(c, f));
+            }
+        });
+    }
+}
+
+pub fn fold(s: &str, ignore_diacritics: bool) -> Cow<'_, str> {
+    if s.is_ascii() {
+        if !s.bytes().any(|b| b.is_ascii_uppercase()) {
+            return Cow::Borrowed(s);
+        }
+        return Cow::Owned(s.to_ascii_lowercase());
+    }
+    let mut folded = String::with_capacity(s.len());
+    fold_chars(s, ignore_diacritics, |_c, f| folded.push(f));
+    return Cow::Owned(folded);
+}
+
+/// Every folded char of `s` with the char it came from and the byte range in `s` of the grapheme
+/// it belongs to, so whatever is found in the folded form can be drawn on the original text.
+pub fn fold_spans(s: &str, ignore_diacritics: bool) -> Vec<(char, char, Range<usize>)> {
+    let mut spans = Vec::with_capacity(s.len());
+    if s.is_ascii() {
+        spans.extend(s.char_indices().map(|(i, c)| (c, c.to_ascii_lowercase(), i..i + 1)));
+        return spans;
+    }
+    // a grapheme is as small as composing and decomposing ever reaches
+    for (i, grapheme) in s.grapheme_indices(true) {
+        fold_chars(grapheme, ignore_diacritics, |c, f| spans.push((c, f, i..i + grapheme.len())));
+    }
+    return spans;
+}
+
+/// Where the already folded `needle` first shows up in `s`, as a byte range of `s`.
+pub fn find_folded(s: &str, needle: &str, ignore_diacritics: bool) -> Option<Range<usize>> {
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() {
+        return None;
+    }
+    let spans = fold_spans(s, ignore_diacritics);
+    let at = spans.windows(needle.len())
+        .position(|window| window.iter().map(|span| span.1).eq(needle.iter().copied()))?;
+    return Some(spans[at].2.start..spans[at + needle.len() - 1].2.end);
+}
+
+/// The longest start of `s` that fits in `max_bytes` without splitting a grapheme, so an emoji
+/// or an accented letter is either shown whole or not at all.
+pub fn truncate_graphemes(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = 0;
+    for (i, grapheme) in s.grapheme_indices(true) {
+        if i + grapheme.len() > max_bytes {
+            break;
+        }
+        end = i + grapheme.len();
+    }
+    return &s[..end];
+}
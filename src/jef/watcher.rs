@@ -0,0 +1,189 @@
+/*
+ * Keeps the index live while JEF is open. Every indexed directory gets an inotify watch, and the
+ * index thread drains the queue each time it wakes up, so files created, deleted or moved during
+ * a session show up in (or drop out of) the fuzzy finder without a full re-walk. inotify only
+ * reports direct children, which is why every directory needs its own watch. If the kernel queue
+ * overflows events were lost, so the index thread falls back to the mtime check from the cache
+ * and re-walks just the directories that changed.
+ *
+ * The index thread sleeps until there is something to do, so a small pump thread blocks on the
+ * inotify fd for it and only says when events are waiting, reading them is still left to poll().
+ */
+
+#[cfg(target_os = "linux")]
+use std::{
+    collections::HashMap,
+    os::unix::io::{AsRawFd, RawFd},
+    thread,
+};
+#[cfg(target_os = "linux")]
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor},
+    unistd::{close, pipe, write},
+};
+#[cfg(target_os = "linux")]
+use crossbeam_channel::{bounded, Sender};
+use crossbeam_channel::Receiver;
+
+use crate::jef::path::EntryPath;
+
+#[derive(Debug, PartialEq)]
+pub enum WatchEvent {
+    Created { path: EntryPath, stack: u16, is_dir: bool },
+    Removed { path: EntryPath, stack: u16 },
+    // written or chmod'ed, only the metadata record needs refreshing
+    Modified { path: EntryPath, stack: u16 },
+    Overflow,
+}
+
+#[cfg(target_os = "linux")]
+pub struct Watcher {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, (EntryPath, u16)>,
+    out_of_watches: bool,
+    wakeups: Receiver<()>,
+    // written to once on drop, which is the pump's cue to close the fds and stop
+    stop: RawFd,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    pub fn new() -> Option<Watcher> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC).ok()?;
+        let Ok((stop_read, stop)) = pipe() else {
+            let _ = close(inotify.as_raw_fd());
+            return None;
+        };
+        // unbuffered, so the pump waits until the index thread comes for a wakeup before polling again
+        let (wake, wakeups) = bounded(0);
+        let fd = inotify.as_raw_fd();
+        thread::spawn(move || pump(fd, stop_read, wake));
+        return Some(Watcher {
+            inotify,
+            dirs: HashMap::new(),
+            out_of_watches: false,
+            wakeups,
+            stop,
+        });
+    }
+
+    /// Gets a message whenever poll() has something to read.
+    pub fn wakeups(&self) -> &Receiver<()> {
+        return &self.wakeups;
+    }
+
+    // returns false once the kernel refuses more watches (fs.inotify.max_user_watches)
+    pub fn watch(&mut self, dir: &EntryPath, stack: u16) -> bool {
+        if self.out_of_watches {
+            return false;
+        }
+        let mask = AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_ATTRIB
+            | AddWatchFlags::IN_ONLYDIR
+            | AddWatchFlags::IN_DONT_FOLLOW;
+        match self.inotify.add_watch(dir.as_path(), mask) {
+            Ok(wd) => {
+                self.dirs.insert(wd, (dir.clone(), stack));
+                return true;
+            },
+            Err(Errno::ENOSPC) => {
+                self.out_of_watches = true;
+                return false;
+            },
+            Err(_) => return true,
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        while let Ok(raw_events) = self.inotify.read_events() {
+            for event in raw_events {
+                if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+                    events.push(WatchEvent::Overflow);
+                    continue;
+                }
+                if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+                    self.dirs.remove(&event.wd);
+                    continue;
+                }
+                let Some((dir, stack)) = self.dirs.get(&event.wd) else {
+                    continue;
+                };
+                let Some(name) = event.name.as_ref() else {
+                    continue;
+                };
+                let path = dir.join(name);
+                let stack = stack + 1;
+                if event.mask.intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO) {
+                    let is_dir = event.mask.contains(AddWatchFlags::IN_ISDIR);
+                    events.push(WatchEvent::Created { path, stack, is_dir });
+                } else if event.mask.intersects(AddWatchFlags::IN_DELETE | AddWatchFlags::IN_MOVED_FROM) {
+                    events.push(WatchEvent::Removed { path, stack });
+                } else if event.mask.intersects(AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_ATTRIB) {
+                    events.push(WatchEvent::Modified { path, stack });
+                }
+            }
+        }
+        return events;
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // the pump closes the inotify fd itself once it is out of poll, Inotify never closes it
+        let _ = write(self.stop, &[1]);
+        let _ = close(self.stop);
+    }
+}
+
+// a wakeup that nobody takes again gets retried on the next readiness, so at worst the index
+// thread polls once for nothing after it already drained everything
+#[cfg(target_os = "linux")]
+fn pump(inotify: RawFd, stop: RawFd, wake: Sender<()>) {
+    loop {
+        let mut fds = [PollFd::new(inotify, PollFlags::POLLIN), PollFd::new(stop, PollFlags::POLLIN)];
+        match poll(&mut fds, -1) {
+            Ok(_) => {},
+            Err(Errno::EINTR) => continue,
+            Err(_) => break,
+        }
+        if fds[1].revents().is_some_and(|events| !events.is_empty()) {
+            break;
+        }
+        if wake.send(()).is_err() {
+            break;
+        }
+    }
+    let _ = close(inotify);
+    let _ = close(stop);
+}
+
+// no inotify off linux, the index just stays as it was walked
+#[cfg(not(target_os = "linux"))]
+pub struct Watcher;
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    pub fn new() -> Option<Watcher> {
+        return None;
+    }
+
+    pub fn wakeups(&self) -> &Receiver<()> {
+        unreachable!("new() never makes one");
+    }
+
+    pub fn watch(&mut self, _dir: &EntryPath, _stack: u16) -> bool {
+        return false;
+    }
+
+    pub fn poll(&mut self) -> Vec<WatchEvent> {
+        return Vec::new();
+    }
+}
@@ -1,4 +1,8 @@
+#![allow(clippy::needless_return)]
+
+// the first tests are kept the way they were written, before clippy ran over this crate
 #[cfg(test)]
+#[allow(unused_imports, clippy::bool_assert_comparison)]
 mod tests;
 
 mod jef;
@@ -10,25 +14,52 @@ use crate::jef::{
         init_index_search,
     },
     term_emu::explorer,
-    flags::Flag,
+    control::{Control, Controller},
+    flags::SearchMode,
+    frecency::Frecency,
+    simd::{self, Level},
 };
 use std::sync::{Arc, Mutex};
 
 
+// `jef --self-check` compares every SIMD level this CPU has against the scalar loops, for packagers
+fn self_check() -> bool {
+    let mut passed = true;
+    for level in Level::available() {
+        match simd::self_check(level) {
+            Ok(()) => println!("{:?}: ok", level),
+            Err(err) => {
+                println!("{}", err);
+                passed = false;
+            },
+        }
+    }
+    println!("using {:?}", simd::level());
+    return passed;
+}
+
 fn main() {
+    if std::env::args().any(|arg| arg == "--self-check") {
+        std::process::exit(if self_check() { 0 } else { 1 });
+    }
     let root = ".";
-    let flag = Arc::new(Mutex::new(Flag::Nothing));
     let search_term = Arc::new(Mutex::new(String::new()));
+    let search_mode = Arc::new(Mutex::new(SearchMode::Fuzzy));
+    let show_ignored = Arc::new(Mutex::new(false));
+    let finder_error = Arc::new(Mutex::new(None));
+    let browser_error = Arc::new(Mutex::new(None));
+    let frecency = Arc::new(Mutex::new(Frecency::load()));
 
-    let (index_thread, shared_file_map) = init_indexer(flag.clone(), root);
-    let (browser_thread, browser_paths) = init_browser(flag.clone(), search_term.clone());
-    let (search_thread, search) = init_index_search(flag.clone(), shared_file_map.clone(), search_term.clone());
+    let mut controller = Controller::new();
+    let search_inbox = controller.inbox();
+    let (index_thread, shared_scope, index_progress) = init_indexer(controller.inbox(), root, show_ignored.clone(), search_inbox.sender());
+    let (browser_thread, browser_paths) = init_browser(controller.inbox(), browser_error.clone());
+    let pager = search_inbox.sender();
+    let (search_thread, search) = init_index_search(search_inbox, shared_scope.clone(), finder_error.clone(), frecency.clone());
     
-    let _ = explorer(flag.clone(), search, browser_paths, search_term);
+    let _ = explorer(controller.clone(), pager, search, browser_paths, search_term, search_mode, show_ignored, finder_error, browser_error, frecency, shared_scope, index_progress);
      
-    if let Ok(mut flag) = flag.lock(){
-        *flag = Flag::Halt;
-    };
+    controller.send(Control::Halt);
     index_thread.join().unwrap();
     browser_thread.join().unwrap();
     search_thread.join().unwrap();
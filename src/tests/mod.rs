@@ -1,12 +1,130 @@
 
 use super::jef::indexer::{
+    get_hashset,
+    init_index_search,
+    IndexProgress,
+    Listing,
+    SharedList,
     last_chars_until_forward_slash,
     starts_with_prefix_simd,
     get_hash,
+    PAGE_SIZE,
+    trigrams,
 }; 
+use super::jef::fuzzy::{fuzzy_positions, fuzzy_score, typo_score};
+use super::jef::highlight::{fit, Highlight};
+use super::jef::terms::Terms;
+use super::jef::grep::{
+    grep_files,
+    parse_hit,
+};
+use super::jef::cache::{
+    refresh,
+    mtime_of,
+    walk_subtree,
+    CachedEntry,
+};
+use super::jef::filter::IndexFilter;
+use super::jef::path::{
+    escape,
+    EntryPath,
+};
+use super::jef::flags::SearchMode;
+use super::jef::query::parse_pattern;
+use super::jef::frecency::Frecency;
+use super::jef::simd::{
+    self,
+    Level,
+};
+use super::jef::unicode::{
+    fold,
+    truncate_graphemes,
+};
+use super::jef::meta::{
+    split_filters,
+    matches_all,
+    EntryMeta,
+};
+use super::jef::watcher::{
+    Watcher,
+    WatchEvent,
+};
+use super::jef::control::{
+    Control,
+    Controller,
+    Wake,
+};
+use super::jef::roots::{
+    project_root,
+    root_path,
+    RootScope,
+};
 use super::jef::opener::{
     Config,
+    IndexerConfig,
+};
+use super::jef::snapshot::{
+    Entry,
+    IndexBuilder,
+    Root,
+    SharedIndex,
+    Snapshot,
+    SEGMENT_SIZE,
 };
+
+// a scratch directory for the tests that need real files, named after the test, the process and
+// a counter so parallel test binaries never share one, and removed again on drop even when an
+// assert panics halfway through
+struct TempTree {
+    root: std::path::PathBuf,
+}
+
+impl TempTree {
+    fn new(name: &str) -> TempTree {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let next = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let root = std::env::temp_dir()
+            .join(format!("jef_{}_test_{}_{}", name, std::process::id(), next));
+        std::fs::create_dir_all(&root).unwrap();
+        return TempTree { root };
+    }
+
+    fn path(&self) -> &std::path::Path {
+        return &self.root;
+    }
+}
+
+impl Drop for TempTree {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+// a published index of `paths` below `root`, each as deep as it is below the root
+fn index_of(root: Root, paths: impl IntoIterator<Item = String>) -> SharedIndex {
+    let index: SharedIndex = std::sync::Arc::new(arc_swap::ArcSwap::from_pointee(Snapshot::default()));
+    let mut builder = IndexBuilder::new().rooted(root.clone());
+    for path in paths {
+        let depth = root.relative(&path).matches('/').count() as u16 + 1;
+        builder.push(&EntryPath::from(path), depth, None);
+    }
+    builder.publish(&index);
+    return index;
+}
+
+// polls the search thread's list until `until` makes something of it, for at most ten seconds
+fn settle<T>(list: &SharedList, until: impl Fn(&Listing) -> Option<T>) -> T {
+    let started = std::time::Instant::now();
+    loop {
+        let settled = until(&list.lock().unwrap());
+        if let Some(settled) = settled {
+            return settled;
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
 #[test]
 fn test_last_chars_until_forward_slash() {
     // Test cases
@@ -50,3 +168,847 @@ fn test_config(){
     let result = Config::default_config();
     println!("{:?}", result);
 }
+
+#[test]
+fn test_fuzzy_score() {
+    // subsequence that is not a prefix still matches
+    assert!(fuzzy_score("./src/main.rs", "mrs", true).is_some());
+    assert!(fuzzy_score("./FEATURE_REQUESTS.md", "req", true).is_some());
+    assert!(fuzzy_score("./src/main.rs", "rsm", true).is_none());
+    assert!(fuzzy_score("./src/main.rs", "", true).is_none());
+
+    // word boundaries and shorter paths rank higher
+    let boundary = fuzzy_score("./src/jef/term_emu.rs", "te", true).unwrap();
+    let middle = fuzzy_score("./src/jef/latest.rs", "te", true).unwrap();
+    assert!(boundary > middle);
+    let short = fuzzy_score("./main.rs", "main", true).unwrap();
+    let long = fuzzy_score("./a/very/deep/tree/of/dirs/main.rs", "main", true).unwrap();
+    assert!(short > long);
+
+    // camel humps count as boundaries
+    let camel = fuzzy_score("./FileMap.rs", "fm", true).unwrap();
+    let flat = fuzzy_score("./filemap.rs", "fm", true).unwrap();
+    assert!(camel > flat);
+}
+
+#[test]
+fn test_segment_queries() {
+    // the last segment goes against the name, the others against directories further up in order
+    assert!(fuzzy_score("./src/jef/indexer.rs", "jef/ind", true).is_some());
+    assert!(fuzzy_score("./src/tests/mod.rs", "te/mod", true).is_some());
+    assert!(fuzzy_score("./src/jef/indexer.rs", "src/ind", true).is_some());
+    assert!(fuzzy_score("/home/me/src/jef/indexer.rs", "sr/jf/idx", true).is_some());
+    assert!(fuzzy_score("./src/tests/mod.rs", "mod/te", true).is_none());
+    assert!(fuzzy_score("./src/jef/indexer.rs", "jef/src", true).is_none());
+    // a name that happens to contain the directory's letters is not a directory
+    assert!(fuzzy_score("./src/jefindexer.rs", "jef/ind", true).is_none());
+    assert!(fuzzy_score("./src/jef/mod.rs", "jef/", true).is_some());
+    assert!(fuzzy_score("./src/jef", "jef/", true).is_none());
+
+    // the closer the segments sit to each other, and the better each one matches, the higher
+    let adjacent = fuzzy_score("./src/jef/indexer.rs", "src/ind", true).unwrap();
+    let skipped = fuzzy_score("./src/a/b/indexer.rs", "src/ind", true).unwrap();
+    assert!(adjacent > skipped);
+    let prefix = fuzzy_score("./src/jef/indexer.rs", "jef/ind", true).unwrap();
+    let scattered = fuzzy_score("./src/joe_ef/indexer.rs", "jef/ind", true).unwrap();
+    assert!(prefix > scattered);
+}
+
+#[test]
+fn test_typo_score() {
+    let typo = |candidate: &str, query: &str, max_typos: usize| typo_score(candidate, &query.chars().collect::<Vec<char>>(), max_typos, true);
+    // swapped, missing, extra and wrong characters are one typo each
+    assert!(fuzzy_score("./src/jef/term_emu.rs", "temr_emu", true).is_none());
+    assert!(typo("./src/jef/term_emu.rs", "temr_emu", 1).is_some());
+    assert!(typo("./src/jef/indexer.rs", "indxer", 1).is_some());
+    assert!(typo("./src/jef/indexer.rs", "inddexer", 1).is_some());
+    assert!(typo("./src/jef/indexer.rs", "imdexer", 1).is_some());
+    assert!(typo("./src/jef/indexer.rs", "imdxer.r", 1).is_none());
+    assert!(typo("./src/jef/indexer.rs", "imdxer.r", 2).is_some());
+    assert!(typo("./src/jef/indexer.rs", "imdexer", 0).is_none());
+    // short queries get no slack, one typo in three characters would match half the index
+    assert!(typo("./src/jef/fuzzy.rs", "fyz", 1).is_none());
+    // the typo has to be in the name, and a slashed query lines up by segment or not at all
+    assert!(typo("./src/jef/indexer.rs", "jfe_", 1).is_none());
+    assert!(typo("./src/jef/indexer.rs", "jef/imdexer", 1).is_none());
+    // only the start of the name counts, the characters further on cannot make up for the typos
+    assert!(typo("./src/jef/indexer_with_a_long_tail.rs", "imdexer", 1).is_some());
+    assert!(typo("./src/jef/xedni_indexer.rs", "indexer", 1).is_none());
+    assert!(typo("./docs/Ünïcode_notes.md", "unicdoe", 1).is_some());
+
+    // anything that really matches ranks above a typo, and fewer typos above more
+    let matched = fuzzy_score("./a/very/deep/tree/of/dirs/that/goes/on/and/on/terminal_emulator.rs", "termemu", true).unwrap();
+    let one = typo("./term_emu.rs", "temr_emu", 2).unwrap();
+    let two = typo("./term_emu.rs", "temr_eum", 2).unwrap();
+    assert!(matched > one);
+    assert!(one > two);
+}
+
+#[test]
+fn test_highlight() {
+    let marked = |path: &str, ranges: Vec<std::ops::Range<usize>>| -> Vec<String> {
+        ranges.into_iter().map(|range| path[range].to_string()).collect()
+    };
+    // the alignment the score came from, not the first chars that happen to fit
+    let path = "./src/main.rs";
+    assert_eq!(marked(path, fuzzy_positions(path, "mrs", true).unwrap()), vec!["m", "r", "s"]);
+    assert_eq!(fuzzy_positions(path, "mrs", true).unwrap()[0], 6..7);
+    let path = "./src/jef/indexer.rs";
+    assert_eq!(marked(path, fuzzy_positions(path, "jef/ind", true).unwrap()), vec!["j", "e", "f", "i", "n", "d"]);
+    assert_eq!(fuzzy_positions(path, "jef/ind", true).unwrap()[0], 6..7);
+    let path = "./Résumé/Straße.md";
+    assert_eq!(marked(path, fuzzy_positions(path, "strasse", true).unwrap()).concat(), "Straße");
+
+    let highlight = |highlight: Highlight, path: &str| -> Vec<String> {
+        marked(path, highlight.ranges(&EntryPath::from(path), 0))
+    };
+    let fuzzy = |query: &str| Highlight::Fuzzy { terms: Terms::parse(query, true), max_typos: 1, ignore_diacritics: true };
+    // overlapping and touching matches come out as one
+    assert_eq!(highlight(fuzzy("main ^src .rs$"), "./src/main.rs"), vec!["src", "main.rs"]);
+    assert_eq!(highlight(fuzzy("temr_emu !test"), "./src/jef/term_emu.rs"), vec!["term_emu"]);
+    let prefix = Highlight::NamePrefix { terms: Terms::parse("ma", true), ignore_diacritics: true };
+    assert_eq!(highlight(prefix, "./src/main.rs"), vec!["ma"]);
+    let pattern = parse_pattern(r"re:(\w+)\.rs$", SearchMode::Fuzzy).unwrap().unwrap();
+    assert_eq!(highlight(Highlight::Pattern(pattern), "./src/main.rs"), vec!["main"]);
+    let glob = parse_pattern("*.rs", SearchMode::Fuzzy).unwrap().unwrap();
+    assert!(highlight(Highlight::Pattern(glob), "./src/main.rs").is_empty());
+
+    // cut around the match, the name when nothing matched
+    let path = "./a/very/deep/tree/of/dirs/main.rs";
+    let shown = |runs: Vec<(String, bool)>| runs.into_iter().map(|(run, matched)| if matched { format!("[{}]", run) } else { run }).collect::<String>();
+    assert_eq!(shown(fit(path, &[], 50)), path);
+    assert_eq!(shown(fit(path, &[], 12)), "…irs/main.rs");
+    assert_eq!(shown(fit(path, &[27..29, 29..31], 12)), "…irs/[main].rs");
+    assert_eq!(shown(fit(path, &[4..6, 6..8], 12)), "…/a/[very]/de…");
+    assert_eq!(shown(fit(path, &[0..1, 2..3], 12)), "[.]/[a]/very/de…");
+}
+
+#[test]
+fn test_extended_terms() {
+    let fuzzy = |query: &str, path: &str| Terms::parse(query, true).score(path, |path, term| fuzzy_score(path, term, true));
+
+    // every term has to match, in any order
+    assert!(fuzzy("ind jef", "./src/jef/indexer.rs").is_some());
+    assert!(fuzzy("ind tests", "./src/jef/indexer.rs").is_none());
+    assert!(fuzzy("ind jef", "./src/jef/indexer.rs") > fuzzy("ind", "./src/jef/indexer.rs"));
+
+    assert!(fuzzy("'dex", "./src/jef/indexer.rs").is_some());
+    assert!(fuzzy("'dxr", "./src/jef/indexer.rs").is_none());
+    assert!(fuzzy("^src .rs$", "./src/jef/indexer.rs").is_some());
+    assert!(fuzzy("^jef", "./src/jef/indexer.rs").is_none());
+    assert!(fuzzy("^SRC/MAIN.RS$", "./src/main.rs").is_some());
+    assert!(fuzzy("^src/main$", "./src/main.rs").is_none());
+    assert!(fuzzy("rs !test", "./src/tests/mod.rs").is_none());
+    assert!(fuzzy("rs !test", "./src/main.rs").is_some());
+    assert!(fuzzy("rs !.rs$", "./src/main.rs").is_none());
+    assert!(fuzzy("rs !^src", "./lib/main.rs").is_some());
+
+    // nothing but operators is no query at all
+    assert!(Terms::parse("! ' ^", true).is_empty());
+    assert_eq!(Terms::parse("!tmp ^src main", true).lookup(), Some("main"));
+    assert_eq!(Terms::parse("!tmp 'main", true).lookup(), None);
+
+    // the local finder keeps matching plain terms as a name prefix
+    let prefix = |query: &str, name: &str| Terms::parse(query, true).score(name, |_path, term| name.starts_with(term).then_some(0));
+    assert!(prefix("ma !.md", "main.rs").is_some());
+    assert!(prefix("ma !.md", "manual.md").is_none());
+    assert!(prefix("ain", "main.rs").is_none());
+}
+
+#[test]
+fn test_trigrams() {
+    let path = trigrams("./jef.config.toml");
+    // every trigram of a substring is a trigram of the whole path
+    for gram in trigrams("config") {
+        assert!(path.contains(&gram));
+    }
+    // case and the leading ./ do not matter
+    assert_eq!(trigrams("./CONFIG"), trigrams("config"));
+    assert!(trigrams("ab").is_empty());
+}
+
+#[test]
+fn test_cache_refresh() {
+    let tree = TempTree::new("cache");
+    let root = tree.path();
+    std::fs::create_dir_all(root.join("a")).unwrap();
+    std::fs::write(root.join("a/b.txt"), "b").unwrap();
+    std::fs::write(root.join("gone.txt"), "gone").unwrap();
+
+    let root_str = root.to_str().unwrap().to_string();
+    let filter = IndexFilter::new(root, false, &IndexerConfig::default());
+    let dir_entry = |path: String, depth: u16| {
+        let path = EntryPath::from(path);
+        let dir_mtime = mtime_of(path.as_path());
+        let meta = EntryMeta::of(path.as_path(), false);
+        CachedEntry { path, depth, dir_mtime, meta }
+    };
+    let file_entry = |path: String, depth: u16| {
+        let path = EntryPath::from(path);
+        let meta = EntryMeta::of(path.as_path(), false);
+        CachedEntry { path, depth, dir_mtime: None, meta }
+    };
+    let cached = vec![
+        dir_entry(root_str.clone(), 0),
+        dir_entry(format!("{}/a", root_str), 1),
+        file_entry(format!("{}/a/b.txt", root_str), 2),
+        file_entry(format!("{}/gone.txt", root_str), 1),
+    ];
+
+    // nothing moved yet
+    let (cached, changed) = refresh(cached, &filter);
+    assert!(!changed);
+
+    std::fs::write(root.join("a/c.txt"), "c").unwrap();
+    std::fs::write(root.join("a/b.txt"), "bigger").unwrap();
+    std::fs::create_dir_all(root.join("new/deep")).unwrap();
+    std::fs::remove_file(root.join("gone.txt")).unwrap();
+    let (cached, changed) = refresh(cached, &filter);
+    assert!(changed);
+    // files are only stat'ed again in a directory that was listed again
+    let b = cached.iter().find(|entry| entry.path.ends_with("/a/b.txt")).unwrap();
+    assert_eq!(b.meta.map(|meta| meta.size), Some(6));
+    let paths: Vec<&str> = cached.iter().map(|entry| &*entry.path).collect();
+    assert!(paths.contains(&format!("{}/a/c.txt", root_str).as_str()));
+    assert!(paths.contains(&format!("{}/new/deep", root_str).as_str()));
+    assert!(!paths.contains(&format!("{}/gone.txt", root_str).as_str()));
+
+    let (cached, changed) = refresh(cached, &filter);
+    assert!(!changed);
+
+    // a new ignore file decides about what was cached below it, and the cache keeps track of it
+    let filter = IndexFilter::new(root, true, &IndexerConfig::default());
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    std::fs::write(root.join("a/.gitignore"), "b.txt\n").unwrap();
+    let (cached, changed) = refresh(cached, &filter);
+    assert!(changed);
+    let paths: Vec<&str> = cached.iter().map(|entry| &*entry.path).collect();
+    assert!(!paths.contains(&format!("{}/a/b.txt", root_str).as_str()));
+    assert!(paths.contains(&format!("{}/a/c.txt", root_str).as_str()));
+    assert_eq!(paths.iter().filter(|path| path.ends_with("/a/c.txt")).count(), 1);
+    let rules = filter.rule_files(cached.iter().map(|entry| entry.path.as_path()));
+    assert!(rules.contains(&root.join("a/.gitignore")));
+    assert!(rules.contains(&root.join(".jefignore")));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_watcher_events() {
+    let tree = TempTree::new("watch");
+    let root = tree.path();
+    let root_str = root.to_str().unwrap().to_string();
+
+    let mut watcher = Watcher::new().unwrap();
+    assert!(watcher.watch(&EntryPath::new(root), 0));
+    std::fs::write(root.join("new.txt"), "new").unwrap();
+    std::fs::create_dir(root.join("sub")).unwrap();
+    std::fs::remove_file(root.join("new.txt")).unwrap();
+
+    // the pump says so as soon as there is something to read
+    assert!(watcher.wakeups().recv_timeout(std::time::Duration::from_secs(5)).is_ok());
+    let events = watcher.poll();
+    let path = |name: &str| EntryPath::from(format!("{}/{}", root_str, name));
+    assert!(events.contains(&WatchEvent::Created { path: path("new.txt"), stack: 1, is_dir: false }));
+    assert!(events.contains(&WatchEvent::Created { path: path("sub"), stack: 1, is_dir: true }));
+    assert!(events.contains(&WatchEvent::Removed { path: path("new.txt"), stack: 1 }));
+}
+
+#[test]
+fn test_ignore_rules() {
+    let tree = TempTree::new("ignore");
+    let root = tree.path();
+    std::fs::create_dir_all(root.join("target/debug")).unwrap();
+    std::fs::create_dir_all(root.join("src/nested")).unwrap();
+    std::fs::create_dir_all(root.join(".git/objects")).unwrap();
+    std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+    std::fs::write(root.join(".jefignore"), "secret.txt\n").unwrap();
+    std::fs::write(root.join("src/nested/.gitignore"), "!keep.log\n").unwrap();
+    for file in ["target/debug/jef", "src/main.rs", "src/out.log", "src/nested/keep.log", "secret.txt"] {
+        std::fs::write(root.join(file), "").unwrap();
+    }
+    let root_str = root.to_str().unwrap().to_string();
+    let walked = |filter: &IndexFilter| -> Vec<String> {
+        walk_subtree(filter, root, 0).into_iter()
+            .map(|entry| entry.path[root_str.len() + 1..].to_string())
+            .collect()
+    };
+
+    let respected = walked(&IndexFilter::new(root, true, &IndexerConfig::default()));
+    assert!(respected.contains(&"src/main.rs".to_string()));
+    assert!(respected.contains(&"src/nested/keep.log".to_string()));
+    assert!(!respected.contains(&"src/out.log".to_string()));
+    assert!(!respected.contains(&"secret.txt".to_string()));
+    assert!(!respected.iter().any(|path| path.starts_with("target")));
+    assert!(!respected.iter().any(|path| path.starts_with(".git/")));
+
+    let everything = walked(&IndexFilter::new(root, false, &IndexerConfig::default()));
+    assert!(everything.contains(&"target/debug/jef".to_string()));
+    assert!(everything.contains(&"src/out.log".to_string()));
+}
+
+#[test]
+fn test_index_scope() {
+    let tree = TempTree::new("scope");
+    let root = tree.path();
+    std::fs::create_dir_all(root.join("src/deep/deeper")).unwrap();
+    std::fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+    for file in ["src/main.rs", "src/notes.md", "src/deep/deeper/far.rs", "node_modules/pkg/index.rs"] {
+        std::fs::write(root.join(file), "").unwrap();
+    }
+    let root_str = root.to_str().unwrap().to_string();
+    let config = IndexerConfig {
+        exclude: vec!["node_modules".to_string()],
+        include: vec!["*.rs".to_string()],
+        max_depth: Some(3),
+        ..IndexerConfig::default()
+    };
+    let filter = IndexFilter::new(root, false, &config);
+    let walked: Vec<String> = walk_subtree(&filter, root, 0).into_iter()
+        .map(|entry| entry.path[root_str.len() + 1..].to_string())
+        .collect();
+    assert!(walked.contains(&"src/main.rs".to_string()));
+    assert!(walked.contains(&"src/deep/deeper".to_string()));
+    assert!(!walked.contains(&"src/notes.md".to_string()));
+    assert!(!walked.contains(&"src/deep/deeper/far.rs".to_string()));
+    assert!(!walked.iter().any(|path| path.starts_with("node_modules")));
+}
+
+#[test]
+fn test_grep_files() {
+    let tree = TempTree::new("grep");
+    let root = tree.path();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/main.rs"), "fn main() {\n    let Needle = 1;\n}\n").unwrap();
+    std::fs::write(root.join("src/lib.rs"), "// needle in a comment\n").unwrap();
+    std::fs::write(root.join("blob.bin"), b"needle\0\x01\x02").unwrap();
+    let root_str = root.to_str().unwrap();
+    let paths: Vec<std::sync::Arc<EntryPath>> = ["src/main.rs", "src/lib.rs", "blob.bin", "src"].iter()
+        .map(|path| std::sync::Arc::new(EntryPath::from(format!("{}/{}", root_str, path))))
+        .collect();
+
+    let hits = grep_files(&paths, "needle");
+    assert_eq!(&**hits[0], format!("{}/src/lib.rs:1: // needle in a comment", root_str));
+    assert_eq!(&**hits[1], format!("{}/src/main.rs:2: let Needle = 1;", root_str));
+    assert_eq!(hits.len(), 2);
+    assert_eq!(grep_files(&paths, "Needle").len(), 1);
+    assert_eq!(parse_hit(&hits[1]), Some((root.join("src/main.rs").as_path(), 2)));
+    assert_eq!(parse_hit(&EntryPath::from("nowhere.rs:3: x")), None);
+}
+
+#[test]
+fn test_parse_pattern() {
+    let regex = parse_pattern("re:^mod\\.rs$", SearchMode::Fuzzy).unwrap().unwrap();
+    assert!(regex.is_match("./src/jef/mod.rs"));
+    assert!(!regex.is_match("./src/jef/mod.rs.bak"));
+    let regex = parse_pattern("re:jef/.*\\.RS", SearchMode::Fuzzy).unwrap().unwrap();
+    assert!(!regex.is_match("./src/jef/indexer.rs"));
+
+    let glob = parse_pattern("src/*/mod.rs", SearchMode::Fuzzy).unwrap().unwrap();
+    assert!(glob.is_match("./src/jef/mod.rs"));
+    assert!(!glob.is_match("./src/jef/deep/mod.rs"));
+    let glob = parse_pattern("**/*.rs", SearchMode::Infix).unwrap().unwrap();
+    assert!(glob.is_match("./src/jef/deep/mod.rs"));
+    assert!(glob.is_match("./main.rs"));
+    let glob = parse_pattern("*.toml", SearchMode::Glob).unwrap().unwrap();
+    assert!(glob.is_match("./a/b/Cargo.toml"));
+
+    assert!(parse_pattern("main", SearchMode::Fuzzy).unwrap().is_none());
+    assert!(parse_pattern("main", SearchMode::Grep).unwrap().is_none());
+    assert!(parse_pattern("main", SearchMode::Regex).unwrap().is_some());
+    assert!(parse_pattern("re:(unclosed", SearchMode::Fuzzy).unwrap_err().starts_with("regex: "));
+    assert!(parse_pattern("glob:[abc", SearchMode::Fuzzy).unwrap_err().starts_with("glob: "));
+}
+
+#[test]
+fn test_meta_filters() {
+    let tree = TempTree::new("meta");
+    let root = tree.path();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/main.rs"), vec![b'x'; 2048]).unwrap();
+    std::fs::write(root.join("notes.TXT"), "").unwrap();
+    let meta_of = |path: &str| EntryMeta::of(&root.join(path), false);
+    let main_rs = root.join("src/main.rs").to_str().unwrap().to_string();
+    let notes = root.join("notes.TXT").to_str().unwrap().to_string();
+    let src = root.join("src").to_str().unwrap().to_string();
+    let passes = |query: &str, path: &str, name: &str| {
+        let (filters, _rest) = split_filters(query).unwrap();
+        matches_all(&filters, path, meta_of(name).as_ref())
+    };
+
+    let (filters, rest) = split_filters("main  type:f ext:rs").unwrap();
+    assert_eq!(filters.len(), 2);
+    assert_eq!(rest, "main");
+    assert_eq!(split_filters("fn  main").unwrap().1, "fn  main");
+    assert!(passes("type:f ext:rs,toml", &main_rs, "src/main.rs"));
+    assert!(passes("ext:txt", &notes, "notes.TXT"));
+    assert!(!passes("type:f", &src, "src"));
+    assert!(passes("type:d", &src, "src"));
+    assert!(passes("size:>1k size:<=2k", &main_rs, "src/main.rs"));
+    assert!(!passes("size:>2KB", &main_rs, "src/main.rs"));
+    assert!(passes("mtime:<1h owner:me perm:rw", &main_rs, "src/main.rs"));
+    assert!(!passes("mtime:>1d", &main_rs, "src/main.rs"));
+    assert!(!passes("perm:x", &main_rs, "src/main.rs"));
+    assert!(!matches_all(&split_filters("type:f").unwrap().0, &main_rs, None));
+    assert!(split_filters("size:huge").is_err());
+    assert!(split_filters("type:socket").is_err());
+}
+
+#[test]
+fn test_frecency() {
+    let tree = TempTree::new("frecency");
+    let root = tree.path();
+    for dir in ["work/project", "work/proto", "old/project"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    let root = std::fs::canonicalize(root).unwrap();
+    let now = 1_000_000_000;
+    let mut frecency = Frecency::default();
+    // three visits a month ago lose to two visits just now
+    for _ in 0..3 {
+        frecency.visit(&root.join("old/project"), now - 30 * 24 * 60 * 60);
+    }
+    frecency.visit(&root.join("work/project"), now);
+    frecency.visit(&root.join("work/project"), now);
+    frecency.visit(&root.join("work/proto"), now);
+
+    assert_eq!(frecency.best_dir("proj", &root, now), Some(root.join("work/project")));
+    assert_eq!(frecency.best_dir("old proj", &root, now), Some(root.join("old/project")));
+    assert_eq!(frecency.best_dir("proj", &root.join("work/project"), now), Some(root.join("old/project")));
+    // the last word has to be in the last component
+    assert_eq!(frecency.best_dir("work", &root, now), None);
+
+    let scores = frecency.scores_under(&root.join("work"), now);
+    assert!(scores["./project"] > scores["./proto"]);
+    assert_eq!(scores.len(), 2);
+
+    std::fs::remove_dir_all(root.join("work/project")).unwrap();
+    assert_eq!(frecency.best_dir("project", &root, now), Some(root.join("old/project")));
+    assert_eq!(frecency.scores_under(&root.join("work"), now).len(), 1);
+}
+
+#[test]
+fn test_unicode_folding() {
+    // precomposed and decomposed spellings fold the same, with or without accents counted
+    assert_eq!(fold("RÉSUMÉ", false), fold("re\u{301}sume\u{301}", false));
+    assert_eq!(fold("résumé", true), "resume");
+    assert_ne!(fold("résumé", false), "resume");
+    assert_eq!(fold("Straße", true), "strasse");
+    assert_eq!(fold("ΟΔΟΣ", true), fold("οδος", true));
+
+    assert!(fuzzy_score("./docs/Résumé.pdf", "resume", true).is_some());
+    assert!(fuzzy_score("./docs/Résumé.pdf", "resume", false).is_none());
+    assert!(fuzzy_score("./docs/re\u{301}sume\u{301}.pdf", "résumé", false).is_some());
+
+    // long multibyte names on both sides of the 16 byte chunks
+    let path = "./日本語のディレクトリ/もう一つ/👨‍👩‍👧‍👦_family_photo_アルバム.jpg";
+    assert_eq!(last_chars_until_forward_slash(path), "👨‍👩‍👧‍👦_family_photo_アルバム.jpg");
+    assert_eq!(last_chars_until_forward_slash("ディレクトリ/なまえ"), "なまえ");
+    assert!(starts_with_prefix_simd("a_rather_long_file_name.txt", "a_rather_long_file"));
+    assert!(!starts_with_prefix_simd("a_rather_long_file_name.txt", "a_rather_long_fila"));
+    assert!(!starts_with_prefix_simd("short", "a_rather_long_file_name"));
+
+    assert_eq!(truncate_graphemes("ae\u{301}", 2), "a");
+    assert_eq!(truncate_graphemes("👨‍👩‍👧 ok", 10), "");
+}
+
+#[test]
+fn test_non_utf8_paths() {
+    use std::os::unix::ffi::OsStrExt;
+    let tree = TempTree::new("bytes");
+    let root = tree.path();
+    let name = std::ffi::OsStr::from_bytes(b"caf\xe9 \xff\xfe.txt");
+    std::fs::create_dir_all(root.join(name).with_extension("d")).unwrap();
+    std::fs::write(root.join(name), "needle\n").unwrap();
+    assert_eq!(escape(name), "caf\\xe9 \\xff\\xfe.txt");
+
+    // found by the walker, matched by its escaped text, reachable by its real bytes
+    let filter = IndexFilter::new(root, false, &IndexerConfig::default());
+    let walked = walk_subtree(&filter, root, 0);
+    let file = walked.iter().find(|entry| entry.path.file_name() == "caf\\xe9 \\xff\\xfe.txt").unwrap();
+    assert_eq!(file.path.as_path(), root.join(name));
+    assert!(file.meta.is_some());
+    let dir = walked.iter().find(|entry| entry.path.file_name().ends_with(".d")).unwrap();
+    assert!(dir.dir_mtime.is_some());
+
+    let hits = grep_files(&[std::sync::Arc::new(file.path.clone())], "needle");
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0].ends_with(".txt:1: needle"));
+    assert_eq!(parse_hit(&hits[0]), Some((root.join(name).as_path(), 1)));
+
+    // a later refresh keeps them instead of treating them as gone
+    let (refreshed, changed) = refresh(walked.clone(), &filter);
+    assert!(!changed);
+    assert_eq!(refreshed.len(), walked.len());
+}
+
+#[test]
+fn test_simd_matches_scalar() {
+    // every level this machine has must agree with the scalar loops, and so must whatever got picked
+    assert!(Level::available().contains(&Level::Scalar));
+    for level in Level::available() {
+        assert_eq!(simd::self_check(level), Ok(()));
+    }
+    assert!(simd::level().is_supported());
+
+    let path = "./a/very/long/directory/name/that/spans/several/vector/widths/file.rs";
+    for level in Level::available() {
+        assert_eq!(simd::rfind_byte_using(level, path.as_bytes(), b'/'), Some(path.len() - "file.rs".len() - 1));
+        assert_eq!(simd::rfind_byte_using(level, b"no slash anywhere in here at all", b'/'), None);
+        assert!(simd::starts_with_using(level, path.as_bytes(), b"./a/very/long/directory/name/that"));
+        assert!(!simd::starts_with_using(level, path.as_bytes(), b"./a/very/long/directory/name/thaT"));
+        assert!(!simd::starts_with_using(level, b"short", path.as_bytes()));
+    }
+}
+
+fn live(snapshot: &Snapshot) -> impl Iterator<Item = &Entry> {
+    return snapshot.segments().iter().flat_map(|segment| segment.live());
+}
+
+#[test]
+fn test_index_snapshot() {
+    use rayon::iter::ParallelIterator;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+    let index: SharedIndex = Arc::new(arc_swap::ArcSwap::from_pointee(Snapshot::default()));
+    let mut builder = IndexBuilder::new();
+    assert_eq!(index.load().generation, 0);
+
+    // readers keep loading while the builder publishes, and never see a snapshot go backwards
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let (index, done) = (index.clone(), done.clone());
+        std::thread::spawn(move || {
+            let mut last = (0, 0);
+            while !done.load(Ordering::Relaxed) {
+                let snapshot = index.load_full();
+                let seen = (snapshot.generation, live(&snapshot).count());
+                assert!(seen.0 >= last.0 && seen.1 >= last.1);
+                last = seen;
+            }
+        })
+    };
+    let total = SEGMENT_SIZE * 2 + 10;
+    for i in 0..total {
+        builder.push(&EntryPath::from(format!("./dir{}/file{}.rs", i % 7, i)), 2, None);
+        if i % 5000 == 0 {
+            builder.publish(&index);
+        }
+    }
+    builder.publish(&index);
+    done.store(true, Ordering::Relaxed);
+    reader.join().unwrap();
+
+    let snapshot = index.load_full();
+    assert_eq!(live(&snapshot).count(), total);
+    assert_eq!(snapshot.stack, 2);
+    let hash = *get_hashset(2, "file12345.rs").last().unwrap();
+    let hits: Vec<String> = snapshot.bucket(hash).map(|entry| snapshot.path(entry).to_string()).collect();
+    // the bucket hash collides plenty, the fuzzy search sorts those out by prefix
+    assert!(hits.iter().any(|hit| hit == "./dir4/file12345.rs"));
+    let grams = trigrams("file3276");
+    assert_eq!(snapshot.segments().iter().map(|segment| segment.with_trigrams(&grams).len()).sum::<usize>(), 11);
+    assert!(builder.contains(&EntryPath::from("./dir0/file0.rs"), 2));
+    assert!(builder.contains(&EntryPath::from(format!("./dir{}/file{}.rs", (total - 1) % 7, total - 1)), 2));
+
+    // a publish with nothing new keeps the old snapshot, edits share the untouched segments
+    builder.publish(&index);
+    assert_eq!(index.load().generation, snapshot.generation);
+    builder.remove_tree(&EntryPath::from("./dir3"));
+    builder.update_meta(&EntryPath::from("./dir0/file7.rs"), 2, Some(EntryMeta::default()));
+    builder.publish(&index);
+    let edited = index.load_full();
+    assert_eq!(edited.generation, snapshot.generation + 1);
+    assert!(live(&edited).all(|entry| !edited.path(entry).starts_with("./dir3/")));
+    assert!(live(&edited).any(|entry| &**edited.path(entry) == "./dir0/file7.rs" && entry.meta.is_some()));
+    assert_eq!(live(&snapshot).count(), total);
+    assert!(!builder.contains(&EntryPath::from("./dir3/file3.rs"), 2));
+}
+
+#[test]
+fn test_path_tree() {
+    use std::os::unix::ffi::OsStrExt;
+    let index: SharedIndex = std::sync::Arc::new(arc_swap::ArcSwap::from_pointee(Snapshot::default()));
+    let mut builder = IndexBuilder::new();
+    let odd = EntryPath::new(std::path::Path::new(std::ffi::OsStr::from_bytes(b"./src/caf\xe9.rs")));
+    for path in ["./src", "./src/main.rs", "./src/jef", "./src/jef/indexer.rs"] {
+        builder.push(&EntryPath::from(path), path.matches('/').count() as u16, None);
+    }
+    builder.push(&odd, 2, None);
+    builder.publish(&index);
+
+    // paths come back exactly as they went in, raw bytes included
+    let snapshot = index.load_full();
+    let mut paths: Vec<EntryPath> = live(&snapshot).map(|entry| (*snapshot.path(entry)).clone()).collect();
+    paths.sort();
+    assert!(paths.contains(&odd));
+    assert_eq!(paths.iter().find(|path| path.as_bytes() == odd.as_bytes()), Some(&odd));
+    assert!(paths.contains(&EntryPath::from("./src/jef/indexer.rs")));
+
+    // "." and "src" are shared by everything below them, so six nodes hold five paths
+    let stats = snapshot.stats();
+    assert_eq!(stats.entries, 5);
+    assert_eq!(stats.nodes, 6);
+    assert!(stats.path_bytes > 0 && stats.total_bytes() >= stats.path_bytes + stats.bucket_bytes);
+
+    // removing a directory takes its subtree along, and the same path comes back under its old node
+    builder.remove_tree(&EntryPath::from("./src/jef"));
+    builder.publish(&index);
+    assert_eq!(index.load().stats().entries, 3);
+    builder.push(&EntryPath::from("./src/jef"), 2, None);
+    builder.publish(&index);
+    assert_eq!(index.load().stats().nodes, 6);
+}
+
+#[test]
+fn test_control_messages() {
+    use std::time::{Duration, Instant};
+    let mut controller = Controller::new();
+    let mut first = controller.inbox();
+    let mut second = controller.inbox();
+
+    // every worker hears everything, and whatever piled up comes out in one go
+    controller.send(Control::SearchChanged { term: "a".to_string(), mode: SearchMode::Fuzzy });
+    controller.send(Control::Reindex);
+    assert_eq!(first.wait(), vec![Control::SearchChanged { term: "a".to_string(), mode: SearchMode::Fuzzy }, Control::Reindex]);
+    assert_eq!(second.check().len(), 2);
+    assert!(first.check().is_empty());
+
+    // a paused worker stays inside wait until it is resumed, then gets what came in meanwhile
+    controller.send(Control::Pause);
+    controller.send(Control::DirChanged("/tmp".into()));
+    let resume = {
+        let controller = controller.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            controller.send(Control::Resume);
+        })
+    };
+    let started = Instant::now();
+    assert_eq!(first.wait(), vec![Control::DirChanged("/tmp".into())]);
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    resume.join().unwrap();
+
+    // Halt gets through a pause and cuts the batch short
+    controller.send(Control::Pause);
+    controller.send(Control::Halt);
+    controller.send(Control::Reindex);
+    assert_eq!(first.wait(), vec![Control::Halt]);
+
+    // other channels wake a worker too, but the inbox comes first once both have something
+    let (events, receiver) = crossbeam_channel::unbounded();
+    events.send(7).unwrap();
+    let (_idle, quiet) = crossbeam_channel::unbounded::<i32>();
+    assert!(matches!(first.wait_with(&[&quiet, &receiver]), Wake::Control(messages) if messages == vec![Control::Reindex]));
+    assert!(matches!(first.wait_with(&[&quiet, &receiver]), Wake::Other((1, 7))));
+}
+
+#[test]
+fn test_streaming_search() {
+    use std::sync::{Arc, Mutex};
+    let index = index_of(Root::new(std::path::Path::new(".")), (0..SEGMENT_SIZE * 2 + 10).map(|i| format!("./dir{}/file{}.rs", i % 7, i)));
+    let mut controller = Controller::new();
+    let frecency = Arc::new(Mutex::new(Frecency::default()));
+    let scope = Arc::new(arc_swap::ArcSwap::from_pointee(vec![index.clone()]));
+    let (search_thread, list) = init_index_search(controller.inbox(), scope, Arc::new(Mutex::new(None)), frecency);
+
+    // the second query supersedes the first, and no batch ever mixes the two
+    for term in ["file1234", "file2345"] {
+        controller.send(Control::SearchChanged { term: term.to_string(), mode: SearchMode::Infix });
+    }
+    let paths = settle(&list, |list| {
+        let paths = &list.paths;
+        assert!(paths.iter().all(|path| path.contains("file1234")) || paths.iter().all(|path| path.contains("file2345")));
+        let done = !list.is_searching() && list.generation > 0 && paths.iter().all(|path| path.contains("file2345"));
+        return done.then(|| paths.clone());
+    });
+    assert_eq!(paths.len(), 11);
+    assert_eq!(&**paths[0], "./dir0/file2345.rs");
+    let list = list.lock().unwrap();
+    assert_eq!((list.scanned, list.total), (3, 3));
+    drop(list);
+    controller.send(Control::Halt);
+    search_thread.join().unwrap();
+}
+
+#[test]
+fn test_index_progress() {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    use std::time::{Duration, Instant};
+    let tree = TempTree::new("progress");
+    let root = tree.path();
+    for dir in ["a/b/c", "d", "target/debug"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+    std::fs::write(root.join("a/b/file.rs"), "").unwrap();
+
+    // every directory found gets read sooner or later, the ignored ones are never found at all
+    let pending = Arc::new(AtomicUsize::new(1));
+    let filter = IndexFilter::new(root, true, &IndexerConfig::default()).counting(pending.clone());
+    let mut dirs = 0;
+    for entry in filter.walker(root, 0).min_depth(1).into_iter().flatten() {
+        dirs += entry.file_type.is_dir() as usize;
+        assert!(pending.load(Ordering::Relaxed) <= 5);
+    }
+    assert_eq!(dirs, 4);
+    assert_eq!(pending.load(Ordering::Relaxed), 0);
+
+    let mut builder = IndexBuilder::new();
+    for path in ["./a", "./a/b", "./d"] {
+        builder.push(&EntryPath::from(path), 1, None);
+    }
+    builder.remove_tree(&EntryPath::from("./a"));
+    let index: SharedIndex = Arc::new(arc_swap::ArcSwap::from_pointee(Snapshot::default()));
+    builder.publish(&index);
+    assert_eq!(index.load().entry_count(), 1);
+
+    let mut progress = IndexProgress { started: Some(Instant::now()), ..IndexProgress::default() };
+    assert!(!progress.is_done());
+    progress.took = Some(Duration::from_secs(3));
+    assert!(progress.is_done());
+    assert_eq!(progress.elapsed(), Duration::from_secs(3));
+}
+
+#[test]
+fn test_named_roots() {
+    use std::sync::{Arc, Mutex};
+    let roots: std::collections::BTreeMap<String, String> = [("src", "~/src"), ("notes", "/tmp/notes")].into_iter()
+        .map(|(name, path)| (name.to_string(), path.to_string()))
+        .collect();
+    assert_eq!(RootScope::parse("", &roots), Ok(RootScope::Project));
+    assert_eq!(RootScope::parse(".", &roots), Ok(RootScope::CurrentDir));
+    assert_eq!(RootScope::parse(" notes", &roots), Ok(RootScope::Named("notes".to_string())));
+    assert_eq!(RootScope::parse("all", &roots), Ok(RootScope::All));
+    assert!(RootScope::parse("music", &roots).is_err());
+    assert_eq!(RootScope::All.names(&roots), vec!["notes", "src"]);
+    assert!(RootScope::CurrentDir.names(&roots).is_empty());
+    // without a home directory there is nothing to expand `~` to
+    if let Some(home) = std::env::var_os("HOME") {
+        assert_eq!(root_path("~/src"), std::path::PathBuf::from(home).join("src"));
+    }
+    assert_eq!(root_path("/tmp/notes"), std::path::PathBuf::from("/tmp/notes"));
+
+    // one search covers every tree in scope, and swapping the scope is enough to search again
+    let trees: Vec<SharedIndex> = [("src", ["/src/one.rs", "/src/lib.rs"]), ("notes", ["/notes/one.md", "/notes/todo.md"])].iter()
+        .map(|(name, paths)| {
+            let root = Root::new(std::path::Path::new("/").join(name).as_path()).labelled(name);
+            index_of(root, paths.iter().map(|path| path.to_string()))
+        })
+        .collect();
+    let scope = Arc::new(arc_swap::ArcSwap::from_pointee(trees.clone()));
+    let mut controller = Controller::new();
+    let search_inbox = controller.inbox();
+    let search = search_inbox.sender();
+    let (search_thread, list) = init_index_search(search_inbox, scope.clone(), Arc::new(Mutex::new(None)), Arc::new(Mutex::new(Frecency::default())));
+    let found = |generation: u64| -> Vec<String> {
+        return settle(&list, |list| {
+            let done = list.generation == generation && !list.is_searching();
+            return done.then(|| list.paths.iter()
+                .map(|path| format!("{}:{}", list.root_of(path).and_then(|root| root.label()).unwrap_or_default(), &path[list.offset(path)..]))
+                .collect());
+        });
+    };
+    controller.send(Control::SearchChanged { term: "one".to_string(), mode: SearchMode::Infix });
+    assert_eq!(found(1), vec!["notes:one.md", "src:one.rs"]);
+    // the root's own path is not part of what gets matched
+    controller.send(Control::SearchChanged { term: "notes".to_string(), mode: SearchMode::Fuzzy });
+    assert_eq!(found(2), Vec::<String>::new());
+    controller.send(Control::SearchChanged { term: "^one".to_string(), mode: SearchMode::Fuzzy });
+    assert_eq!(found(3), vec!["notes:one.md", "src:one.rs"]);
+    scope.store(Arc::new(vec![trees[1].clone()]));
+    search.send(Control::IndexChanged).unwrap();
+    assert_eq!(found(4), vec!["notes:one.md"]);
+    controller.send(Control::Halt);
+    search_thread.join().unwrap();
+}
+
+#[test]
+fn test_result_paging() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    // two roots that overlap, the second with one file more
+    let trees: Vec<SharedIndex> = [0, 1].iter()
+        .map(|extra| index_of(Root::new(std::path::Path::new("/src")), (0..PAGE_SIZE + 500 + extra).map(|i| format!("/src/dir{}/file{}.rs", i % 3, i))))
+        .collect();
+    let scope = Arc::new(arc_swap::ArcSwap::from_pointee(trees));
+    let mut controller = Controller::new();
+    let search_inbox = controller.inbox();
+    let search = search_inbox.sender();
+    let (search_thread, list) = init_index_search(search_inbox, scope, Arc::new(Mutex::new(None)), Arc::new(Mutex::new(Frecency::default())));
+    let settled = |length: usize| -> (u64, Vec<String>, usize) {
+        return settle(&list, |list| {
+            let done = list.generation > 0 && !list.is_searching() && list.paths.len() == length;
+            return done.then(|| (list.generation, list.paths.iter().map(|path| path.to_string()).collect(), list.more));
+        });
+    };
+
+    // a page at a time, every path once however many roots it is under
+    controller.send(Control::SearchChanged { term: "file".to_string(), mode: SearchMode::Fuzzy });
+    let (generation, first, more) = settled(PAGE_SIZE);
+    assert_eq!(more, 501);
+    search.send(Control::MoreResults(generation)).unwrap();
+    let (_generation, all, more) = settled(PAGE_SIZE + 501);
+    assert_eq!(more, 0);
+    assert_eq!(all[..PAGE_SIZE], first[..]);
+    let unique: std::collections::HashSet<&String> = all.iter().collect();
+    assert_eq!(unique.len(), all.len());
+
+    // an old search's page is never shown over a newer one
+    controller.send(Control::SearchChanged { term: "file1".to_string(), mode: SearchMode::Fuzzy });
+    let (_generation, _paths, more) = settled(PAGE_SIZE);
+    search.send(Control::MoreResults(generation)).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let stale = list.lock().unwrap();
+    assert_eq!((stale.paths.len(), stale.more), (PAGE_SIZE, more));
+    drop(stale);
+    controller.send(Control::Halt);
+    search_thread.join().unwrap();
+}
+
+#[test]
+fn test_project_root() {
+    let tree = TempTree::new("project");
+    let root = tree.path();
+    std::fs::create_dir_all(root.join("app/src/jef")).unwrap();
+    std::fs::create_dir_all(root.join("app/.git")).unwrap();
+    std::fs::create_dir_all(root.join("app/web/ui")).unwrap();
+    std::fs::write(root.join("app/web/package.json"), "{}").unwrap();
+    let markers: Vec<String> = [".git", "Cargo.toml", "package.json"].iter().map(|marker| marker.to_string()).collect();
+
+    // the nearest marker wins, so a package inside a repository is a project of its own
+    assert_eq!(project_root(&root.join("app/src/jef"), &markers), Some(root.join("app")));
+    assert_eq!(project_root(&root.join("app"), &markers), Some(root.join("app")));
+    assert_eq!(project_root(&root.join("app/web/ui"), &markers), Some(root.join("app/web")));
+    assert_eq!(project_root(&root.join("app/web/ui"), &[".git".to_string()]), Some(root.join("app")));
+    assert_eq!(project_root(&root.join("app/src"), &["jef.marker".to_string()]), None);
+
+    assert_eq!(RootScope::Project.toggle_project(), RootScope::CurrentDir);
+    assert_eq!(RootScope::CurrentDir.toggle_project(), RootScope::Project);
+    assert_eq!(RootScope::All.toggle_project(), RootScope::Project);
+
+    // searches only see the path below the project, so its own name matches nothing and the
+    // anchors and globs hold wherever in the project the finder was opened
+    use std::sync::{Arc, Mutex};
+    let project = std::path::Path::new("/home/me/crate");
+    let index = index_of(Root::new(project), ["src", "src/main.rs", "src/crates.rs", "README.md"].map(|path| format!("/home/me/crate/{}", path)));
+    let scope = Arc::new(arc_swap::ArcSwap::from_pointee(vec![index]));
+    let mut controller = Controller::new();
+    let (search_thread, list) = init_index_search(controller.inbox(), scope, Arc::new(Mutex::new(None)), Arc::new(Mutex::new(Frecency::default())));
+    let mut generation = 0;
+    let mut found = |term: &str, mode: SearchMode| -> Vec<String> {
+        controller.send(Control::SearchChanged { term: term.to_string(), mode });
+        generation += 1;
+        return settle(&list, |list| {
+            let done = list.generation == generation && !list.is_searching();
+            return done.then(|| list.paths.iter().map(|path| path[list.offset(path)..].to_string()).collect());
+        });
+    };
+    assert_eq!(found("crate", SearchMode::Fuzzy), vec!["src/crates.rs"]);
+    assert_eq!(found("home", SearchMode::Infix), Vec::<String>::new());
+    assert_eq!(found("^src .rs$", SearchMode::Fuzzy), vec!["src/crates.rs", "src/main.rs"]);
+    assert_eq!(found("src/*.rs", SearchMode::Fuzzy), vec!["src/main.rs", "src/crates.rs"]);
+    controller.send(Control::Halt);
+    search_thread.join().unwrap();
+}